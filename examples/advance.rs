@@ -17,23 +17,25 @@ fn main() {
             "Heads up the wheels are spinning\nAcross the plains in valleys deep",
             "To dawn the wheels that sing\nAn unending dream"
         ])
-        .set_logo(logo_path.to_str().expect("Path is an invalid unicode"), CropCircle::True)
-        .set_image(image_path.to_str().expect("Path is an invalid unicode"), ImagePlacement::Top)
+        .set_logo(logo_path, CropCircle::True)
+        .set_image(image_path, ImagePlacement::Top)
         .set_actions(vec![
             Action {
                 activation_type: ActivationType::Protocol,
                 action_content: "Listen".to_string(),
                 arguments: music_url.to_string(),
                 image_url: None,
+                button_style: None,
             },
             Action {
                 activation_type: ActivationType::Protocol,
                 action_content: "Lyric".to_string(),
                 arguments: music_lyric.to_string(),
                 image_url: None,
+                button_style: None,
             }
         ])
         .set_audio(Audio::WinLoopingAlarm5, Loop::True)
         .show()
-        .expect("Failed to show toast notification")
+        .expect("Failed to show toast notification");
 }
\ No newline at end of file
@@ -19,24 +19,26 @@ fn main() {
             "红藕香残玉簟秋。轻解罗裳，独上兰舟。\n云中谁寄锦书来，雁字回时，月满西楼。",
             "花自飘零水自流。一种相思，两处闲愁。\n此情无计可消除，才下眉头，却上心头。"
         ])
-        .set_logo(logo_path.to_str().expect("Path is an invalid unicode"), CropCircle::True)
-        .set_image(image_path.to_str().expect("Path is an invalid unicode"), ImagePlacement::Top)
+        .set_logo(logo_path, CropCircle::True)
+        .set_image(image_path, ImagePlacement::Top)
         .set_actions(vec![
             Action {
                 activation_type: ActivationType::Protocol,
                 action_content: "阅读".to_string(),
                 arguments: read_url.to_string(),
-                image_url: Some(button_read_path.to_string_lossy().to_string()),
+                image_url: Some(button_read_path.into()),
+                button_style: None,
             },
             Action {
                 activation_type: ActivationType::Protocol,
                 action_content: "赏析".to_string(),
                 arguments: appreciation_url.to_string(),
-                image_url: Some(button_appreciation_path.to_string_lossy().to_string()),
+                image_url: Some(button_appreciation_path.into()),
+                button_style: None,
             }
         ])
         .set_audio(Audio::WinLoopingAlarm5, Loop::True)
         .show()
-        .expect("Failed to show toast notification")
+        .expect("Failed to show toast notification");
 }
 
@@ -7,5 +7,5 @@ fn main() {
             "This is a simple toast message"
         ])
         .show()
-        .expect("Failed to show toast notification")
+        .expect("Failed to show toast notification");
 }
\ No newline at end of file
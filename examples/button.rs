@@ -7,19 +7,22 @@ fn main() {
                 activation_type: ActivationType::Protocol,
                 action_content: "Url".to_string(),
                 arguments: "https://www.google.com/".to_string(),
-                image_url: None
+                image_url: None,
+                button_style: None
             },
             Action {
                 activation_type: ActivationType::Protocol,
                  action_content: "File".to_string(),
                  arguments: r"C:\Windows\Web\Screen\img104.jpg".to_string(),
-                 image_url: None
+                 image_url: None,
+                 button_style: None
              },
             Action {
                  activation_type: ActivationType::Protocol,
                  action_content: "Folder".to_string(),
-                 arguments: r"$env:USERPROFILE\Downloads".to_string(),   // PowerShell supports using environment variables
-                 image_url: None
+                 arguments: r"$env:USERPROFILE\Downloads".to_string(),   // Environment variables are expanded for protocol activation
+                 image_url: None,
+                 button_style: None
              }
          ])
          .show()
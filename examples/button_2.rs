@@ -16,15 +16,17 @@ fn main() {
                 activation_type: ActivationType::Protocol,
                 action_content: String::new(),
                 arguments: "https://doc.rust-lang.org/book/".to_string(),
-                image_url: Some(button_appreciation_path.to_string_lossy().to_string()),
+                image_url: Some(button_appreciation_path.into()),
+                button_style: None,
             },
             Action {
                 activation_type: ActivationType::Protocol,
                 action_content: String::new(),
                 arguments: r"C:\Windows\Web\Screen\img101.jpg".to_string(),
-                image_url: Some(button_read_path.to_string_lossy().to_string()),
+                image_url: Some(button_read_path.into()),
+                button_style: None,
             }
         ])
         .show()
-        .expect("Failed to show toast notification")
+        .expect("Failed to show toast notification");
 }
\ No newline at end of file
@@ -0,0 +1,195 @@
+use crate::{powershell, Audio, WinToastNotify};
+
+/// Speaks the notification with the speech synthesizer of Windows when it is shown.
+///
+/// Speech is skipped when the notification is silent. It stops when the notification is activated,
+/// dismissed or expires.
+///
+/// # Examples
+/// ```
+/// use win_toast_notify::{Announce, AnnounceMode, Audio, Loop, Scenario, WinToastNotify};
+///
+/// let toast = WinToastNotify::new()
+///     .set_scenario(Scenario::Alarm)
+///     .set_title("Incubator 3")
+///     .set_messages(vec!["Temperature above 40 °C"])
+///     .set_audio(Audio::WinLoopingAlarm2, Loop::False)
+///     .set_announce(Announce::new().set_voice("Microsoft Zira Desktop").set_rate(-2).set_mode(AnnounceMode::Replace));
+///
+/// assert_eq!(toast.speech_text().as_deref(), Some("Incubator 3. Temperature above 40 °C."));
+///
+/// let silent = toast.set_audio(Audio::Silent, Loop::False);
+/// assert_eq!(silent.speech_text(), None);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Announce {
+    /// The name of an installed voice, such as `Microsoft David Desktop`. The default voice if `None`.
+    pub voice: Option<String>,
+    /// Speaking rate from -10 to 10.
+    pub rate: i32,
+    /// Volume from 0 to 100.
+    pub volume: u32,
+    pub mode: AnnounceMode,
+    /// The text to speak instead of the title and messages.
+    pub text: Option<String>,
+}
+
+/// How speech is combined with the sound of the notification.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AnnounceMode {
+    /// Speak instead of playing the sound.
+    Replace,
+    /// Speak once the sound has played. Windows sounds are given two seconds, sound files are played to the end,
+    /// so a looping sound file is never followed by speech.
+    AfterSound,
+}
+
+impl Default for Announce {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Announce {
+    /// The default voice at normal rate and full volume, after the sound.
+    pub fn new() -> Self {
+        Self {
+            voice: None,
+            rate: 0,
+            volume: 100,
+            mode: AnnounceMode::AfterSound,
+            text: None,
+        }
+    }
+
+    pub fn set_voice(mut self, voice: &str) -> Self {
+        self.voice = Some(voice.to_string());
+        self
+    }
+
+    /// Set the speaking rate, clamped to -10..=10.
+    pub fn set_rate(mut self, rate: i32) -> Self {
+        self.rate = rate.clamp(-10, 10);
+        self
+    }
+
+    /// Set the volume, clamped to 0..=100.
+    pub fn set_volume(mut self, volume: u32) -> Self {
+        self.volume = volume.min(100);
+        self
+    }
+
+    pub fn set_mode(mut self, mode: AnnounceMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn set_text(mut self, text: &str) -> Self {
+        self.text = Some(text.to_string());
+        self
+    }
+}
+
+impl WinToastNotify {
+    /// Speak the notification when it is shown, see [Announce](struct.Announce.html).
+    pub fn set_announce(mut self, announce: Announce) -> Self {
+        self.announce = Some(announce);
+        self
+    }
+
+    /// The text spoken for the notification: the text of the [Announce](struct.Announce.html) setting,
+    /// or the title and messages as sentences.
+    ///
+    /// `None` if no announcement is set, the notification is silent or there is nothing to say.
+    pub fn speech_text(&self) -> Option<String> {
+        let announce = self.announce.as_ref()?;
+        if matches!(self.audio, None | Some(Audio::Silent)) {
+            return None;
+        }
+        let text = match &announce.text {
+            Some(text) => text.trim().to_string(),
+            None => self
+                .title
+                .iter()
+                .chain(self.messages.iter().flatten())
+                .map(|text| text.trim())
+                .filter(|text| !text.is_empty())
+                .fold(String::new(), |acc, text| {
+                    // End each text as a sentence, so the synthesizer pauses between them
+                    let end = match text.ends_with(['.', '!', '?', ':', ';']) {
+                        true => "",
+                        false => ".",
+                    };
+                    format!("{}{}{} ", acc, text, end)
+                })
+                .trim_end()
+                .to_string(),
+        };
+        (!text.is_empty()).then_some(text)
+    }
+
+    /// PowerShell that speaks `speech_text` and waits until it is spoken or the
+    /// notification ends. It runs in the process that shows the notification, which defines `Wait-Toast` and `$Expiration`.
+    ///
+    /// `None` if there is nothing to speak.
+    pub(crate) fn speech_script(&self) -> Option<String> {
+        let announce = self.announce.as_ref()?;
+        let text = self.speech_text()?;
+        let mut script = String::from(
+            "Add-Type -AssemblyName System.Speech\n$Synthesizer = New-Object System.Speech.Synthesis.SpeechSynthesizer\n",
+        );
+        if let Some(voice) = &announce.voice {
+            script.push_str(&format!("$Synthesizer.SelectVoice({})\n", powershell::quote(voice)));
+        }
+        script.push_str(&format!(
+            r#"$Synthesizer.Rate = {}
+$Synthesizer.Volume = {}
+$null = Register-ObjectEvent -InputObject $Synthesizer -EventName SpeakCompleted -SourceIdentifier SpeakCompleted
+$null = $Synthesizer.SpeakAsync({})
+$null = Wait-Toast $Expiration
+$Synthesizer.SpeakAsyncCancelAll()
+$Synthesizer.Dispose()
+"#,
+            announce.rate.clamp(-10, 10),
+            announce.volume.min(100),
+            powershell::quote(&text)
+        ));
+        Some(script)
+    }
+
+    /// Whether the speech replaces the sound of the notification.
+    pub(crate) fn speech_replaces_sound(&self) -> bool {
+        self.announce.as_ref().is_some_and(|announce| announce.mode == AnnounceMode::Replace) && self.speech_text().is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Announce, AnnounceMode, Audio, Loop, WinToastNotify};
+
+    #[test]
+    fn speech_script() {
+        let toast = WinToastNotify::new()
+            .set_title("Incubator 3")
+            .set_audio(Audio::WinLoopingAlarm2, Loop::False)
+            .set_announce(
+                Announce::new()
+                    .set_voice("Microsoft Zira Desktop")
+                    .set_rate(-2)
+                    .set_volume(80)
+                    .set_text("It's hot")
+                    .set_mode(AnnounceMode::Replace),
+            );
+        let script = toast.speech_script().expect("The toast is not silent");
+        assert!(script.contains("$Synthesizer.SelectVoice('Microsoft Zira Desktop')"));
+        assert!(script.contains("$Synthesizer.Rate = -2"));
+        assert!(script.contains("$Synthesizer.Volume = 80"));
+        assert!(script.contains("$Synthesizer.SpeakAsync('It''s hot')"));
+        assert!(script.contains("Wait-Toast $Expiration"));
+        assert!(toast.speech_replaces_sound());
+
+        let silent = toast.set_audio(Audio::Silent, Loop::False);
+        assert_eq!(silent.speech_script(), None);
+        assert!(!silent.speech_replaces_sound());
+    }
+}
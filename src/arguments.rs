@@ -0,0 +1,224 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// Key/value arguments for toast activation, compatible with the
+/// `ToastArguments` format of the Windows Community Toolkit.
+///
+/// Pairs are serialized as `key=value` separated by `;`. The characters `%`, `;` and `=`
+/// are percent-encoded, so keys and values can contain anything. A key may also be added
+/// without a value, in which case only the key is written.
+///
+/// # Examples
+/// ```
+/// use win_toast_notify::ToastArguments;
+///
+/// let args = ToastArguments::new()
+///     .add("action", "reply")
+///     .add("conversationId", 9813)
+///     .add_key("markAsRead");
+/// let encoded = args.encode().expect("Arguments are too long");
+/// assert_eq!(encoded, "action=reply;conversationId=9813;markAsRead");
+///
+/// // Parse the string received on activation
+/// let parsed = ToastArguments::parse(&encoded).expect("Malformed arguments");
+/// assert_eq!(parsed.get("action"), Some("reply"));
+/// assert_eq!(parsed.get_as::<u32>("conversationId"), Some(9813));
+/// assert!(parsed.contains("markAsRead"));
+/// ```
+///
+/// Values reach the activation unchanged, including characters that are special in XML or PowerShell:
+/// ```
+/// use win_toast_notify::{Action, ActivationType, ToastArguments, WinToastNotify};
+/// use xml::reader::{EventReader, XmlEvent};
+///
+/// let args = ToastArguments::new().add("price", "$5").add("note", "<b>\"Tom's\"</b> `$(calc)` $env:TEMP & more");
+/// let toast = WinToastNotify::new().set_actions(vec![Action {
+///     activation_type: ActivationType::Foreground,
+///     action_content: "Buy".to_string(),
+///     arguments: args.encode().unwrap(),
+///     image_url: None,
+///     button_style: None,
+/// }]);
+///
+/// let xml = toast.to_xml().unwrap();
+/// let arguments = EventReader::from_str(&xml)
+///     .into_iter()
+///     .find_map(|event| match event {
+///         Ok(XmlEvent::StartElement { name, attributes, .. }) if name.local_name == "action" => {
+///             attributes.into_iter().find(|attribute| attribute.name.local_name == "arguments")
+///         }
+///         _ => None,
+///     })
+///     .unwrap()
+///     .value;
+/// assert_eq!(ToastArguments::parse(&arguments).unwrap(), args);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ToastArguments {
+    pairs: Vec<(String, Option<String>)>,
+}
+
+impl ToastArguments {
+    /// Maximum length of the encoded arguments accepted by [`ToastArguments::encode`].
+    ///
+    /// Windows limits the size of the whole toast payload, so arguments are kept well below it.
+    pub const MAX_LENGTH: usize = 1024;
+
+    pub fn new() -> Self {
+        Self { pairs: Vec::new() }
+    }
+
+    /// Add a key and its value. An existing value for the same key is replaced.
+    pub fn add(mut self, key: &str, value: impl ToString) -> Self {
+        self.set(key, Some(value.to_string()));
+        self
+    }
+
+    /// Add a key without a value.
+    pub fn add_key(mut self, key: &str) -> Self {
+        self.set(key, None);
+        self
+    }
+
+    /// Remove a key, returning whether it was present.
+    pub fn remove(&mut self, key: &str) -> bool {
+        let len = self.pairs.len();
+        self.pairs.retain(|(k, _)| k != key);
+        self.pairs.len() != len
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        self.pairs.iter().any(|(k, _)| k == key)
+    }
+
+    /// Get the value of a key. Returns `None` if the key is missing or has no value.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.pairs
+            .iter()
+            .find(|(k, _)| k == key)
+            .and_then(|(_, v)| v.as_deref())
+    }
+
+    /// Get the value of a key parsed into `T`, e.g. `get_as::<i32>("id")`.
+    pub fn get_as<T: FromStr>(&self, key: &str) -> Option<T> {
+        self.get(key).and_then(|v| v.parse().ok())
+    }
+
+    /// Get the value of a key as a boolean. Accepts `true`/`false` and `1`/`0`.
+    pub fn get_bool(&self, key: &str) -> Option<bool> {
+        match self.get(key)? {
+            "1" => Some(true),
+            "0" => Some(false),
+            value => value.to_ascii_lowercase().parse().ok(),
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, Option<&str>)> {
+        self.pairs.iter().map(|(k, v)| (k.as_str(), v.as_deref()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.pairs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pairs.is_empty()
+    }
+
+    /// Serialize the arguments, failing if the result is longer than [`ToastArguments::MAX_LENGTH`].
+    pub fn encode(&self) -> Result<String, Box<dyn std::error::Error>> {
+        let encoded = self.to_string();
+        if encoded.len() > Self::MAX_LENGTH {
+            return Err(format!(
+                "Toast arguments are {} bytes long, the limit is {}",
+                encoded.len(),
+                Self::MAX_LENGTH
+            )
+            .into());
+        }
+        Ok(encoded)
+    }
+
+    /// Parse arguments from an activation string.
+    pub fn parse(arguments: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut parsed = Self::new();
+        for pair in arguments.split(';').filter(|pair| !pair.is_empty()) {
+            let (key, value) = match pair.split_once('=') {
+                Some((key, value)) => (decode(key)?, Some(decode(value)?)),
+                None => (decode(pair)?, None),
+            };
+            if key.is_empty() {
+                return Err(format!("Empty key in toast arguments: \"{}\"", pair).into());
+            }
+            parsed.set(&key, value);
+        }
+        Ok(parsed)
+    }
+
+    fn set(&mut self, key: &str, value: Option<String>) {
+        match self.pairs.iter_mut().find(|(k, _)| k == key) {
+            Some(pair) => pair.1 = value,
+            None => self.pairs.push((key.to_string(), value)),
+        }
+    }
+}
+
+impl fmt::Display for ToastArguments {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (key, value)) in self.pairs.iter().enumerate() {
+            if i > 0 {
+                f.write_str(";")?;
+            }
+            f.write_str(&encode(key))?;
+            if let Some(value) = value {
+                write!(f, "={}", encode(value))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for ToastArguments {
+    type Err = Box<dyn std::error::Error>;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl From<ToastArguments> for String {
+    fn from(arguments: ToastArguments) -> Self {
+        arguments.to_string()
+    }
+}
+
+fn encode(s: &str) -> String {
+    let mut encoded = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '%' => encoded.push_str("%25"),
+            ';' => encoded.push_str("%3B"),
+            '=' => encoded.push_str("%3D"),
+            _ => encoded.push(c),
+        }
+    }
+    encoded
+}
+
+fn decode(s: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let mut decoded = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(i) = rest.find('%') {
+        decoded.push_str(&rest[..i]);
+        let escape = rest.get(i..i + 3).unwrap_or(&rest[i..]);
+        decoded.push(match escape {
+            "%25" => '%',
+            "%3B" | "%3b" => ';',
+            "%3D" | "%3d" => '=',
+            _ => return Err(format!("Invalid escape \"{}\" in toast arguments", escape).into()),
+        });
+        rest = &rest[i + escape.len()..];
+    }
+    decoded.push_str(rest);
+    Ok(decoded)
+}
@@ -0,0 +1,144 @@
+use std::fmt::Write;
+use std::path::Path;
+
+use crate::{notifier, powershell, Audio, Loop, MediaSource, WinToastNotify};
+
+/// Extensions of the sound files played by `Audio::From`.
+pub(crate) const AUDIO_EXTENSIONS: [&str; 6] = ["wav", "mp3", "wma", "m4a", "aac", "flac"];
+
+/// Line printed by the helper once the notification is shown.
+pub(crate) const SHOWN: &str = "shown";
+
+/// Defines `Wait-Toast`, which waits for the next event until a time and returns its source identifier,
+/// or nothing if the time is reached.
+const WAIT_FUNCTION: &str = r#"
+function Wait-Toast($Until) {
+    while ([DateTime]::UtcNow -lt $Until) {
+        $Next = Wait-Event -Timeout 1
+        if ($Next) {
+            $Next | Remove-Event
+            return $Next.SourceIdentifier
+        }
+    }
+}
+"#;
+
+impl WinToastNotify {
+    /// The URI of the custom sound, if any, checking that it can be played.
+    ///
+    /// `None` if the sound is replaced by speech.
+    pub(crate) fn custom_audio(&self) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        match &self.audio {
+            Some(Audio::From(source)) if !is_sound_event(source) && !self.speech_replaces_sound() => {
+                Ok(Some(audio_uri(source)?))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Whether the notification needs a helper process to play a sound file or speak.
+    pub(crate) fn needs_helper(&self) -> bool {
+        matches!(&self.audio, Some(Audio::From(source)) if !is_sound_event(source)) || self.speech_text().is_some()
+    }
+
+    /// PowerShell that shows `$ToastNotification`, then plays the sound file at `audio` and speaks the
+    /// announcement, until the notification is activated, dismissed or expires.
+    ///
+    /// The script prints [SHOWN] once the notification is shown, and keeps running while it plays.
+    pub(crate) fn helper_script(&self, audio: Option<&str>) -> Result<String, std::fmt::Error> {
+        let looping = matches!(self.audio_loop, Loop::True);
+        let mut script = String::from(WAIT_FUNCTION);
+        script.push_str(
+            r#"
+            $null = Register-ObjectEvent -InputObject $ToastNotification -EventName Activated -SourceIdentifier ToastActivated
+            $null = Register-ObjectEvent -InputObject $ToastNotification -EventName Dismissed -SourceIdentifier ToastDismissed
+            $null = Register-ObjectEvent -InputObject $ToastNotification -EventName Failed -SourceIdentifier ToastFailed
+            "#,
+        );
+        if let Some(uri) = audio {
+            write!(
+                script,
+                r#"
+                $MediaPlayer = [Windows.Media.Playback.MediaPlayer, Windows.Media, ContentType = WindowsRuntime]::New()
+                $MediaPlayer.Source = [Windows.Media.Core.MediaSource]::CreateFromUri([Uri]{})
+                $MediaPlayer.IsLoopingEnabled = ${}
+                $null = Register-ObjectEvent -InputObject $MediaPlayer -EventName MediaFailed -SourceIdentifier MediaFailed
+                $null = Register-ObjectEvent -InputObject $MediaPlayer -EventName MediaEnded -SourceIdentifier MediaEnded
+                "#,
+                powershell::quote(uri),
+                looping
+            )?;
+        }
+        match self.expiration_time {
+            Some(time) => writeln!(
+                script,
+                "$Expiration = [DateTimeOffset]::FromUnixTimeMilliseconds({}).UtcDateTime",
+                notifier::unix_millis(time)
+            )?,
+            None => script.push_str("$Expiration = [DateTime]::MaxValue\n"),
+        }
+        write!(
+            script,
+            r#"
+            $Notifier.Show($ToastNotification)
+            [Console]::Out.WriteLine('{}')
+            [Console]::Out.Flush()
+            "#,
+            SHOWN
+        )?;
+
+        // Whether speech follows, i.e. the sound ended before the notification did
+        if audio.is_some() {
+            script.push_str(
+                r#"
+                $MediaPlayer.Play()
+                $Ended = Wait-Toast $Expiration
+                $MediaPlayer.Pause()
+                $MediaPlayer.Dispose()
+                $Speak = $Ended -eq 'MediaEnded'
+                "#,
+            );
+        } else if self.speech_replaces_sound() {
+            script.push_str("$Speak = $true\n");
+        } else {
+            // Windows sounds are short, but cannot be waited for
+            script.push_str("$Speak = $null -eq (Wait-Toast ([DateTime]::UtcNow.AddSeconds(2)))\n");
+        }
+        if let Some(speech) = self.speech_script() {
+            write!(script, "if ($Speak) {{\n{}}}\n", speech)?;
+        }
+        Ok(script)
+    }
+}
+
+/// Whether `source` is a Windows sound event such as `ms-winsoundevent:Notification.Reminder`,
+/// which is played by the notification itself.
+pub(crate) fn is_sound_event(source: &str) -> bool {
+    source.trim().to_ascii_lowercase().starts_with("ms-winsoundevent:")
+}
+
+/// The URI of a sound file, checking that it exists and has a supported format.
+pub(crate) fn audio_uri(source: &str) -> Result<String, String> {
+    let source = MediaSource::from(source);
+    let name = match &source {
+        MediaSource::File(path) => {
+            if !path.is_file() {
+                return Err(format!("Sound \"{}\" does not exist", path.display()));
+            }
+            path.to_string_lossy().into_owned()
+        }
+        MediaSource::Http(url) | MediaSource::AppData(url) | MediaSource::AppX(url) => {
+            url.split(['?', '#']).next().unwrap_or_default().to_string()
+        }
+        MediaSource::Bytes(_) => return Err(String::from("Sounds cannot be played from data")),
+    };
+    let extension = Path::new(&name).extension().map(|extension| extension.to_string_lossy().to_ascii_lowercase());
+    match extension {
+        Some(extension) if AUDIO_EXTENSIONS.contains(&extension.as_str()) => Ok(source.uri()),
+        _ => Err(format!(
+            "Unsupported sound \"{}\", the supported formats are {}",
+            name,
+            AUDIO_EXTENSIONS.join(", ")
+        )),
+    }
+}
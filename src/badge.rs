@@ -0,0 +1,95 @@
+use crate::{powershell, ToastNotifier};
+
+/// A badge shown on the app's taskbar button and Start tile.
+///
+/// # Examples
+/// ```
+/// use win_toast_notify::{Badge, BadgeGlyph, ToastNotifier};
+///
+/// assert_eq!(Badge::Count(7).to_xml(), r#"<badge value="7"/>"#);
+/// assert_eq!(Badge::Glyph(BadgeGlyph::NewMessage).to_xml(), r#"<badge value="newMessage"/>"#);
+///
+/// let notifier = ToastNotifier::new(None);
+/// notifier.set_badge(&Badge::Count(7)).expect("Failed to update badge");
+/// notifier.clear_badge().expect("Failed to clear badge");
+/// ```
+///
+/// [Microsoft Docs about Badge](https://learn.microsoft.com/en-us/windows/apps/design/shell/tiles-and-notifications/badges)
+#[derive(Debug, Clone, PartialEq)]
+pub enum Badge {
+    /// A number, Windows shows `99+` for counts above 99 and no badge for 0.
+    Count(u32),
+    Glyph(BadgeGlyph),
+}
+
+impl Badge {
+    pub fn to_xml(&self) -> String {
+        match self {
+            Badge::Count(count) => format!("<badge value=\"{}\"/>", count),
+            Badge::Glyph(glyph) => format!("<badge value=\"{}\"/>", glyph.as_str()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum BadgeGlyph {
+    None,
+    Activity,
+    Alarm,
+    Alert,
+    Attention,
+    Available,
+    Away,
+    Busy,
+    Error,
+    NewMessage,
+    Paused,
+    Playing,
+    Unavailable,
+}
+
+impl BadgeGlyph {
+    pub fn as_str(&self) -> &str {
+        match self {
+            BadgeGlyph::None => "none",
+            BadgeGlyph::Activity => "activity",
+            BadgeGlyph::Alarm => "alarm",
+            BadgeGlyph::Alert => "alert",
+            BadgeGlyph::Attention => "attention",
+            BadgeGlyph::Available => "available",
+            BadgeGlyph::Away => "away",
+            BadgeGlyph::Busy => "busy",
+            BadgeGlyph::Error => "error",
+            BadgeGlyph::NewMessage => "newMessage",
+            BadgeGlyph::Paused => "paused",
+            BadgeGlyph::Playing => "playing",
+            BadgeGlyph::Unavailable => "unavailable",
+        }
+    }
+}
+
+impl ToastNotifier {
+    /// Show a badge for this App ID.
+    pub fn set_badge(&self, badge: &Badge) -> Result<(), Box<dyn std::error::Error>> {
+        let mut command = powershell::load_xml(&badge.to_xml());
+        command.push_str(&self.badge_updater_script());
+        command.push_str("$BadgeUpdater.Update([Windows.UI.Notifications.BadgeNotification]::New($XmlDocument))\n");
+        self.run(&command)?;
+        Ok(())
+    }
+
+    /// Remove the badge of this App ID.
+    pub fn clear_badge(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let mut command = self.badge_updater_script();
+        command.push_str("$BadgeUpdater.Clear()\n");
+        self.run(&command)?;
+        Ok(())
+    }
+
+    fn badge_updater_script(&self) -> String {
+        format!(
+            "$BadgeUpdater = [Windows.UI.Notifications.BadgeUpdateManager, Windows.UI.Notifications, ContentType = WindowsRuntime]::CreateBadgeUpdaterForApplication({})\n",
+            powershell::quote(&self.app_id)
+        )
+    }
+}
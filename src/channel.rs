@@ -0,0 +1,372 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use crate::audio::{self, AUDIO_EXTENSIONS};
+use crate::{Audio, CropCircle, Duration, Header, Loop, MediaSource, Priority, Scenario, WinToastNotify};
+
+/// A category of notifications with default settings, such as "builds" or "alarms".
+///
+/// Notifications target a channel with [set_channel](struct.WinToastNotify.html#method.set_channel), and take its
+/// defaults for the settings they keep at the value of [WinToastNotify::new](struct.WinToastNotify.html#method.new).
+/// Notifications of a disabled channel are not shown.
+///
+/// # Examples
+/// ```
+/// use win_toast_notify::{Audio, Channel, Header, Loop, Scenario, WinToastNotify};
+///
+/// let alarms = Channel::new("alarms")
+///     .set_audio(Audio::WinLoopingAlarm2, Loop::True)
+///     .set_scenario(Scenario::Alarm)
+///     .set_header(Header::new("alarms", "Alarms"));
+///
+/// let mut toast = WinToastNotify::new().set_channel("alarms").set_title("Freezer door open");
+/// alarms.apply(&mut toast);
+/// assert!(matches!(toast.scenario, Scenario::Alarm));
+/// assert!(matches!(toast.audio, Some(Audio::WinLoopingAlarm2)));
+/// assert_eq!(toast.header.map(|header| header.title).as_deref(), Some("Alarms"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Channel {
+    pub name: String,
+    pub enabled: bool,
+    pub audio: Option<(Audio, Loop)>,
+    pub scenario: Option<Scenario>,
+    pub duration: Option<Duration>,
+    pub logo: Option<(MediaSource, CropCircle)>,
+    pub header: Option<Header>,
+    pub priority: Option<Priority>,
+}
+
+/// The channels of a [ToastNotifier](struct.ToastNotifier.html), see
+/// [ToastNotifier::set_channels](struct.ToastNotifier.html#method.set_channels).
+///
+/// Channels are defined in code and can be changed by a config file, so that users can switch channels off or
+/// change their sound. The file has a section per channel, and settings that are not set keep their value:
+///
+/// ```ini
+/// # Comments are lines that start with # or ;, values are taken as is to the end of the line
+/// [builds]
+/// enabled = false
+///
+/// [alarms]
+/// # A Windows sound without the "Win" prefix, "Silent", or a sound file
+/// audio = LoopingAlarm3
+/// loop = true
+/// # none, reminder, alarm, incomingCall or urgent
+/// scenario = alarm
+/// # short or long
+/// duration = long
+/// # default or high
+/// priority = high
+/// logo = C:\My Files #2\alarm.png
+/// logo_circle = true
+/// # The title of a header with the ID of the channel
+/// header = Alarms
+/// ```
+///
+/// # Examples
+/// ```
+/// use win_toast_notify::{Audio, Channel, Channels, Loop, MediaSource};
+///
+/// let channels = Channels::new()
+///     .add_channel(Channel::new("builds").set_audio(Audio::WinIM, Loop::False))
+///     .add_channel(Channel::new("chat"))
+///     .read_config("[builds]\nenabled = false\n\n[alerts]\naudio = Mail")
+///     .expect("Invalid channel config");
+///
+/// assert!(!channels.get("builds").unwrap().enabled);
+/// assert!(channels.get("chat").unwrap().enabled);
+/// assert!(matches!(channels.get("alerts").unwrap().audio, Some((Audio::WinMail, Loop::False))));
+///
+/// let channels = channels.read_config("[chat]\n; The team logo\nlogo = https://example.com/logo.png#v2").unwrap();
+/// assert_eq!(channels.get("chat").unwrap().logo.as_ref().unwrap().0.to_uri().unwrap(), "https://example.com/logo.png#v2");
+///
+/// let channels = channels.read_config("[chat]\nlogo = C:\\My Files #2\\logo.png").unwrap();
+/// let Some((MediaSource::File(path), _)) = &channels.get("chat").unwrap().logo else { panic!("Expected a file") };
+/// assert!(path.to_string_lossy().ends_with(r"My Files #2\logo.png"));
+///
+/// // Unknown sounds are rejected, but sound files are accepted
+/// assert!(Channels::new().read_config("[chat]\naudio = Mial").is_err());
+/// assert!(Channels::new().read_config("[chat]\naudio = C:\\Sounds\\ding.wav").is_ok());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Channels {
+    channels: HashMap<String, Channel>,
+}
+
+impl Channel {
+    /// An enabled channel without defaults.
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            enabled: true,
+            audio: None,
+            scenario: None,
+            duration: None,
+            logo: None,
+            header: None,
+            priority: None,
+        }
+    }
+
+    pub fn set_enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+
+    pub fn set_audio(mut self, audio: Audio, audio_loop: Loop) -> Self {
+        self.audio = Some((audio, audio_loop));
+        self
+    }
+
+    pub fn set_scenario(mut self, scenario: Scenario) -> Self {
+        self.scenario = Some(scenario);
+        self
+    }
+
+    pub fn set_duration(mut self, duration: Duration) -> Self {
+        self.duration = Some(duration);
+        self
+    }
+
+    pub fn set_logo(mut self, source: impl Into<MediaSource>, hint_crop: CropCircle) -> Self {
+        self.logo = Some((source.into(), hint_crop));
+        self
+    }
+
+    pub fn set_header(mut self, header: Header) -> Self {
+        self.header = Some(header);
+        self
+    }
+
+    pub fn set_priority(mut self, priority: Priority) -> Self {
+        self.priority = Some(priority);
+        self
+    }
+
+    /// Set the defaults of the channel on a notification, where it keeps the value of
+    /// [WinToastNotify::new](struct.WinToastNotify.html#method.new).
+    pub fn apply(&self, toast: &mut WinToastNotify) {
+        if let (Some((audio, audio_loop)), Some(Audio::WinDefault), Loop::False) =
+            (&self.audio, &toast.audio, &toast.audio_loop)
+        {
+            toast.audio = Some(audio.clone());
+            toast.audio_loop = audio_loop.clone();
+        }
+        if let (Some(scenario), Scenario::None) = (&self.scenario, &toast.scenario) {
+            toast.scenario = scenario.clone();
+        }
+        if let (Some(duration), Duration::Short) = (&self.duration, &toast.duration) {
+            toast.duration = duration.clone();
+        }
+        if let (Some((logo, hint_crop)), None) = (&self.logo, &toast.logo) {
+            toast.logo = Some(logo.clone());
+            toast.logo_circle = hint_crop.clone();
+        }
+        if toast.header.is_none() {
+            toast.header = self.header.clone();
+        }
+        if let (Some(priority), Priority::Default) = (&self.priority, &toast.priority) {
+            toast.priority = priority.clone();
+        }
+    }
+}
+
+impl Channels {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a channel, replacing the channel with the same name.
+    pub fn add_channel(mut self, channel: Channel) -> Self {
+        self.channels.insert(channel.name.clone(), channel);
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Channel> {
+        self.channels.get(name)
+    }
+
+    /// Change the channels with a config file. Channels that are not defined yet are added.
+    ///
+    /// A missing file leaves the channels unchanged, so the file can be optional.
+    pub fn load_config(self, path: impl AsRef<Path>) -> Result<Self, Box<dyn std::error::Error>> {
+        let path = path.as_ref();
+        match fs::read_to_string(path) {
+            Ok(config) => self
+                .read_config(&config)
+                .map_err(|e| format!("Invalid channel config \"{}\": {}", path.display(), e).into()),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(self),
+            Err(e) => Err(format!("Failed to read channel config \"{}\": {}", path.display(), e).into()),
+        }
+    }
+
+    /// Change the channels with the content of a config file, see [load_config](#method.load_config).
+    pub fn read_config(mut self, config: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut section: Option<String> = None;
+        for (index, line) in config.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(['#', ';']) {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+                let name = name.trim();
+                self.channels.entry(name.to_string()).or_insert_with(|| Channel::new(name));
+                section = Some(name.to_string());
+                continue;
+            }
+            let error = |message: String| format!("line {}: {}", index + 1, message);
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(error(format!("expected `key = value`, found \"{}\"", line)).into());
+            };
+            let Some(channel) = section.as_ref().and_then(|name| self.channels.get_mut(name)) else {
+                return Err(error(String::from("setting outside of a [channel] section")).into());
+            };
+            set(channel, key.trim(), value.trim()).map_err(error)?;
+        }
+        Ok(self)
+    }
+}
+
+/// Change a setting of a channel from the config file.
+fn set(channel: &mut Channel, key: &str, value: &str) -> Result<(), String> {
+    let invalid = || format!("invalid {} \"{}\"", key, value);
+    match key {
+        "enabled" => channel.enabled = parse_bool(value).ok_or_else(invalid)?,
+        "audio" => {
+            let audio_loop = channel.audio.take().map_or(Loop::False, |(_, audio_loop)| audio_loop);
+            channel.audio = Some((parse_audio(value).ok_or_else(invalid)?, audio_loop));
+        }
+        "loop" => {
+            let audio_loop = match parse_bool(value).ok_or_else(invalid)? {
+                true => Loop::True,
+                false => Loop::False,
+            };
+            let audio = channel.audio.take().map_or(Audio::WinDefault, |(audio, _)| audio);
+            channel.audio = Some((audio, audio_loop));
+        }
+        "scenario" => {
+            channel.scenario = Some(match value.to_ascii_lowercase().replace(['_', '-'], "").as_str() {
+                "none" => Scenario::None,
+                "reminder" => Scenario::Reminder,
+                "alarm" => Scenario::Alarm,
+                "incomingcall" => Scenario::IncomingCall,
+                "urgent" => Scenario::Urgent,
+                _ => return Err(invalid()),
+            })
+        }
+        "duration" => {
+            channel.duration = Some(match value.to_ascii_lowercase().as_str() {
+                "short" => Duration::Short,
+                "long" => Duration::Long,
+                _ => return Err(invalid()),
+            })
+        }
+        "priority" => {
+            channel.priority = Some(match value.to_ascii_lowercase().as_str() {
+                "default" => Priority::Default,
+                "high" => Priority::High,
+                _ => return Err(invalid()),
+            })
+        }
+        "logo" => {
+            let hint_crop = channel.logo.take().map_or(CropCircle::False, |(_, hint_crop)| hint_crop);
+            channel.logo = Some((MediaSource::from(value), hint_crop));
+        }
+        "logo_circle" => {
+            let hint_crop = match parse_bool(value).ok_or_else(invalid)? {
+                true => CropCircle::True,
+                false => CropCircle::False,
+            };
+            match &mut channel.logo {
+                Some((_, crop)) => *crop = hint_crop,
+                None => return Err(String::from("logo_circle without a logo")),
+            }
+        }
+        "header" => {
+            let arguments = channel.header.take().map(|header| header.arguments).unwrap_or_default();
+            channel.header = Some(Header::new(&channel.name, value).set_arguments(&arguments));
+        }
+        _ => return Err(format!("unknown setting \"{}\"", key)),
+    }
+    Ok(())
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        "true" | "yes" | "on" | "1" => Some(true),
+        "false" | "no" | "off" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+/// A Windows sound by its name without the `Win` prefix, such as `Mail` or `LoopingAlarm3`, a sound event
+/// such as `ms-winsoundevent:Notification.Looping.Alarm`, or a sound file with a supported format.
+fn parse_audio(value: &str) -> Option<Audio> {
+    let name = value.to_ascii_lowercase();
+    let looping = |prefix: &str, sounds: [Audio; 10]| {
+        let number = name.strip_prefix(prefix)?;
+        let number: usize = match number {
+            "" => 1,
+            number => number.parse().ok()?,
+        };
+        sounds.get(number.checked_sub(1)?).cloned()
+    };
+    let alarms = [
+        Audio::WinLoopingAlarm1,
+        Audio::WinLoopingAlarm2,
+        Audio::WinLoopingAlarm3,
+        Audio::WinLoopingAlarm4,
+        Audio::WinLoopingAlarm5,
+        Audio::WinLoopingAlarm6,
+        Audio::WinLoopingAlarm7,
+        Audio::WinLoopingAlarm8,
+        Audio::WinLoopingAlarm9,
+        Audio::WinLoopingAlarm10,
+    ];
+    let calls = [
+        Audio::WinLoopingCall1,
+        Audio::WinLoopingCall2,
+        Audio::WinLoopingCall3,
+        Audio::WinLoopingCall4,
+        Audio::WinLoopingCall5,
+        Audio::WinLoopingCall6,
+        Audio::WinLoopingCall7,
+        Audio::WinLoopingCall8,
+        Audio::WinLoopingCall9,
+        Audio::WinLoopingCall10,
+    ];
+    match name.as_str() {
+        "silent" => Some(Audio::Silent),
+        "default" => Some(Audio::WinDefault),
+        "im" => Some(Audio::WinIM),
+        "mail" => Some(Audio::WinMail),
+        "reminder" | "remainder" => Some(Audio::WinRemainder),
+        "sms" => Some(Audio::WinSMS),
+        _ => looping("loopingalarm", alarms)
+            .or_else(|| looping("loopingcall", calls))
+            .or_else(|| parse_sound_file(value)),
+    }
+}
+
+/// A sound event or a sound file, recognized by its extension so that a misspelled name is not taken for a file.
+fn parse_sound_file(value: &str) -> Option<Audio> {
+    if audio::is_sound_event(value) {
+        return Some(Audio::From(value.to_string()));
+    }
+    let file = value.split(['?', '#']).next().unwrap_or_default();
+    let extension = Path::new(file).extension()?.to_string_lossy().to_ascii_lowercase();
+    AUDIO_EXTENSIONS.contains(&extension.as_str()).then(|| Audio::From(value.to_string()))
+}
+
+impl WinToastNotify {
+    /// Target a channel of the notifier, taking its defaults, see [Channel](struct.Channel.html).
+    ///
+    /// Notifiers without channels show the notification as it is.
+    pub fn set_channel(mut self, channel: &str) -> Self {
+        self.channel = Some(channel.to_string());
+        self
+    }
+}
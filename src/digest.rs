@@ -0,0 +1,208 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use xml::escape::escape_str_pcdata;
+
+use crate::image_cache::content_hash;
+use crate::{Action, ActivationType, Audio, Clock, Loop, MediaSource, WinToastNotify};
+
+/// Collapses bursts of notifications into one summary, see
+/// [ToastNotifier::set_digest](struct.ToastNotifier.html#method.set_digest).
+///
+/// When more than `threshold` notifications arrive within `window`, the following ones are held and a digest
+/// is shown instead, such as "12 new alerts: 9 from build, 3 from deploy". The digest shows the first titles and
+/// a button that opens the list of all held notifications, and replaces itself by its tag as more arrive.
+/// Notifications are counted by their group. Once a burst is over, notifications are shown again and the next
+/// digest starts empty.
+///
+/// Notifications with progress are never held.
+///
+/// # Examples
+/// ```
+/// use std::time::{Duration, SystemTime};
+/// use win_toast_notify::{Digest, WinToastNotify};
+///
+/// let now = SystemTime::now();
+/// let digest = Digest::new(2, Duration::from_secs(60)).set_label("alerts").set_clock(move || now);
+///
+/// for (group, title) in [("build", "Build 1 failed"), ("build", "Build 2 failed"), ("build", "Build 3 failed"), ("deploy", "Deploy failed")] {
+///     let toast = WinToastNotify::new().set_group(group).set_title(title);
+///     if let Some(summary) = digest.collect(&toast) {
+///         println!("{:?}", summary.title);
+///     }
+/// }
+/// // The first two were shown, the last two are in the digest
+/// let summary = digest.collect(&WinToastNotify::new().set_group("build").set_title("Build 4 failed")).unwrap();
+/// assert_eq!(summary.title.as_deref(), Some("3 new alerts: 2 from build, 1 from deploy"));
+/// assert_eq!(summary.messages, Some(vec!["Build 3 failed".to_string(), "Deploy failed".to_string()]));
+/// ```
+pub struct Digest {
+    threshold: usize,
+    window: Duration,
+    tag: String,
+    label: String,
+    titles: usize,
+    list_uri: Option<String>,
+    template: WinToastNotify,
+    clock: Box<dyn Clock>,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    arrivals: VecDeque<SystemTime>,
+    held: Vec<Held>,
+}
+
+struct Held {
+    group: Option<String>,
+    title: Option<String>,
+    messages: Vec<String>,
+}
+
+impl Digest {
+    /// Hold notifications after `threshold` within `window`.
+    pub fn new(threshold: usize, window: Duration) -> Self {
+        Self {
+            threshold,
+            window,
+            tag: String::from("digest"),
+            label: String::from("notifications"),
+            titles: 2,
+            list_uri: None,
+            template: WinToastNotify::new(),
+            clock: Box::new(SystemTime::now),
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// Set the tag the digest replaces itself by, `digest` by default.
+    pub fn set_tag(mut self, tag: &str) -> Self {
+        self.tag = tag.to_string();
+        self
+    }
+
+    /// Set what the notifications are called in the summary, `notifications` by default.
+    pub fn set_label(mut self, label: &str) -> Self {
+        self.label = label.to_string();
+        self
+    }
+
+    /// Set how many titles of held notifications are shown as messages, 2 by default.
+    pub fn set_titles(mut self, titles: usize) -> Self {
+        self.titles = titles;
+        self
+    }
+
+    /// Open this URI from the button of the digest, instead of a generated HTML list.
+    pub fn set_list_uri(mut self, uri: &str) -> Self {
+        self.list_uri = Some(uri.to_string());
+        self
+    }
+
+    /// Set the notification the digest is based on, such as for its logo or sound.
+    /// Its title, messages, actions and tag are replaced.
+    pub fn set_template(mut self, template: WinToastNotify) -> Self {
+        self.template = template;
+        self
+    }
+
+    pub fn set_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    /// Count a notification, returning the digest to show instead if the notification is held.
+    pub fn collect(&self, toast: &WinToastNotify) -> Option<WinToastNotify> {
+        if toast.progress.is_some() {
+            return None;
+        }
+        let now = self.clock.now();
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let window = self.window;
+        state
+            .arrivals
+            .retain(|arrival| now.duration_since(*arrival).unwrap_or_default() < window);
+        state.arrivals.push_back(now);
+        if state.arrivals.len() <= self.threshold {
+            state.held.clear();
+            return None;
+        }
+
+        state.held.push(Held {
+            group: toast.group.clone(),
+            title: toast.title.clone(),
+            messages: toast.messages.clone().unwrap_or_default(),
+        });
+        Some(self.summary(&state.held))
+    }
+
+    fn summary(&self, held: &[Held]) -> WinToastNotify {
+        // Counts by group in order of first arrival, the largest first
+        let mut groups: Vec<(&str, usize)> = Vec::new();
+        for item in held {
+            let group = item.group.as_deref().unwrap_or("other");
+            match groups.iter_mut().find(|(name, _)| *name == group) {
+                Some((_, count)) => *count += 1,
+                None => groups.push((group, 1)),
+            }
+        }
+        groups.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        let mut title = format!("{} new {}", held.len(), self.label);
+        if held.iter().any(|item| item.group.is_some()) {
+            let counts: Vec<String> = groups.iter().map(|(name, count)| format!("{} from {}", count, name)).collect();
+            title = format!("{}: {}", title, counts.join(", "));
+        }
+
+        let mut toast = self.template.clone();
+        toast.title = Some(title);
+        toast.messages = Some(held.iter().filter_map(|item| item.title.clone()).take(self.titles).collect());
+        toast.tag = Some(self.tag.clone());
+        toast.progress = None;
+        let list = self.list_uri.clone().or_else(|| {
+            let path = write_list(&self.tag, held).ok()?;
+            Some(MediaSource::from(path).uri())
+        });
+        toast.actions = list.map(|uri| {
+            vec![Action {
+                activation_type: ActivationType::Protocol,
+                action_content: String::from("Show all"),
+                arguments: uri,
+                image_url: None,
+                button_style: None,
+            }]
+        });
+        // Updates replace the digest quietly
+        if held.len() > 1 {
+            toast.audio = Some(Audio::Silent);
+            toast.audio_loop = Loop::False;
+            toast.suppress_popup = true;
+        }
+        toast
+    }
+}
+
+/// Write the held notifications to an HTML file in the temporary directory.
+fn write_list(tag: &str, held: &[Held]) -> std::io::Result<PathBuf> {
+    let dir = std::env::temp_dir().join("win-toast-notify");
+    fs::create_dir_all(&dir)?;
+    let path = dir.join(format!("digest-{}.html", content_hash(tag.as_bytes())));
+    let mut html = String::from("<!DOCTYPE html>\n<meta charset=\"utf-8\">\n<title>Notifications</title>\n<ol>\n");
+    for item in held {
+        html.push_str("<li>");
+        if let Some(group) = &item.group {
+            html.push_str(&format!("[{}] ", escape_str_pcdata(group)));
+        }
+        html.push_str(&format!("<b>{}</b>", escape_str_pcdata(item.title.as_deref().unwrap_or_default())));
+        for message in &item.messages {
+            html.push_str(&format!("<br>{}", escape_str_pcdata(message)));
+        }
+        html.push_str("</li>\n");
+    }
+    html.push_str("</ol>\n");
+    fs::write(&path, html)?;
+    Ok(path)
+}
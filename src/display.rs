@@ -0,0 +1,246 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::platform::reg_value;
+use crate::powershell::Options;
+use crate::{MediaSource, WinToastNotify};
+
+/// The theme, contrast and scale images are selected for.
+///
+/// Like the resources of packaged apps, an image can have variants named with qualifiers
+/// between its name and extension, separated by `.` or `_`. For `logo.png` these are e.g.
+/// `logo.theme-dark.png`, `logo.scale-200.png`, `logo.theme-dark_scale-200.png` and `logo.contrast-high.png`.
+///
+/// The variant matching the contrast is preferred, then the one matching the theme, then the one with the
+/// closest scale, rounding up. Variants with a different theme or contrast are never selected, and a file
+/// without a scale qualifier is scale 100.
+///
+/// # Examples
+/// ```
+/// use win_toast_notify::{Contrast, DisplaySettings, Theme};
+///
+/// let variants = ["logo.png", "logo.scale-200.png", "logo.theme-dark.png", "logo.theme-dark.scale-200.png", "logo.contrast-high.png"];
+///
+/// let dark = DisplaySettings { theme: Theme::Dark, contrast: Contrast::Standard, scale: 150 };
+/// assert_eq!(dark.select("logo.png", variants), Some("logo.theme-dark.scale-200.png"));
+///
+/// let light = DisplaySettings { theme: Theme::Light, contrast: Contrast::Standard, scale: 100 };
+/// assert_eq!(light.select("logo.png", variants), Some("logo.png"));
+///
+/// let high_contrast = DisplaySettings { contrast: Contrast::High, ..dark };
+/// assert_eq!(high_contrast.select("logo.png", variants), Some("logo.contrast-high.png"));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DisplaySettings {
+    pub theme: Theme,
+    pub contrast: Contrast,
+    /// Display scale in percent, such as 100, 150 or 200.
+    pub scale: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Theme {
+    Light,
+    Dark,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Contrast {
+    Standard,
+    /// A high contrast theme is on. Matches the `contrast-high`, `contrast-black` and `contrast-white` qualifiers.
+    High,
+}
+
+impl Default for DisplaySettings {
+    /// Light theme, standard contrast, scale 100.
+    fn default() -> Self {
+        Self {
+            theme: Theme::Light,
+            contrast: Contrast::Standard,
+            scale: 100,
+        }
+    }
+}
+
+impl DisplaySettings {
+    /// The settings of the current user: the system theme, high contrast mode and the scale of the primary display.
+    ///
+    /// Settings that cannot be read are taken from the default.
+    pub fn detect() -> Self {
+        Self::detect_with(&Options::default())
+    }
+
+    /// The settings of the current user, read with the timeout and cancellation of a notifier.
+    pub(crate) fn detect_with(options: &Options) -> Self {
+        let default = Self::default();
+        let dword = |key: &str, name: &str| {
+            let value = reg_value(key, name, options)?;
+            u32::from_str_radix(value.trim_start_matches("0x"), 16).ok()
+        };
+        let theme = match dword(
+            r"HKCU\Software\Microsoft\Windows\CurrentVersion\Themes\Personalize",
+            "SystemUsesLightTheme",
+        ) {
+            Some(0) => Theme::Dark,
+            Some(_) => Theme::Light,
+            None => default.theme,
+        };
+        // HCF_HIGHCONTRASTON is the lowest bit of the flags, stored as a decimal string
+        let flags = reg_value(r"HKCU\Control Panel\Accessibility\HighContrast", "Flags", options);
+        let contrast = match flags.and_then(|flags| flags.parse::<u32>().ok()) {
+            Some(flags) if flags & 1 == 1 => Contrast::High,
+            _ => Contrast::Standard,
+        };
+        let scale = dword(r"HKCU\Control Panel\Desktop\WindowMetrics", "AppliedDPI")
+            .map_or(default.scale, |dpi| dpi * 100 / 96);
+        Self { theme, contrast, scale }
+    }
+
+    /// Select the best variant of the file `name` among `candidates`, or `None` if no candidate matches.
+    ///
+    /// `name` itself is a candidate without qualifiers if it is in `candidates`.
+    pub fn select<'a>(&self, name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+        let (stem, extension) = split_name(name);
+        candidates
+            .into_iter()
+            .filter_map(|candidate| Some((self.score(stem, extension, candidate)?, candidate)))
+            .max_by_key(|(score, _)| *score)
+            .map(|(_, candidate)| candidate)
+    }
+
+    /// How well `candidate` matches as a variant of `stem.extension`, higher is better.
+    fn score(&self, stem: &str, extension: &str, candidate: &str) -> Option<(bool, bool, (bool, i64))> {
+        let (candidate_stem, candidate_extension) = split_name(candidate);
+        if !candidate_extension.eq_ignore_ascii_case(extension) {
+            return None;
+        }
+        let qualifiers = if candidate_stem.eq_ignore_ascii_case(stem) {
+            ""
+        } else {
+            let prefix = candidate_stem.get(..stem.len() + 1)?;
+            if !prefix.eq_ignore_ascii_case(&format!("{}.", stem)) {
+                return None;
+            }
+            &candidate_stem[stem.len() + 1..]
+        };
+
+        let (mut contrast, mut theme, mut scale) = (false, false, 100);
+        for qualifier in qualifiers.split(['.', '_']).filter(|q| !q.is_empty()) {
+            let (key, value) = qualifier.split_once('-')?;
+            match (key.to_ascii_lowercase().as_str(), value.to_ascii_lowercase().as_str()) {
+                ("contrast", "standard") if self.contrast == Contrast::Standard => contrast = true,
+                ("contrast", "high" | "black" | "white") if self.contrast == Contrast::High => contrast = true,
+                ("theme", "light") if self.theme == Theme::Light => theme = true,
+                ("theme", "dark") if self.theme == Theme::Dark => theme = true,
+                ("scale", value) => scale = value.parse().ok()?,
+                // A different contrast or theme, or an unknown qualifier
+                _ => return None,
+            }
+        }
+        // The closest scale at or above the display scale, otherwise the largest below it
+        let scale = match scale >= self.scale {
+            true => (true, -(scale as i64)),
+            false => (false, scale as i64),
+        };
+        Some((contrast, theme, scale))
+    }
+}
+
+impl WinToastNotify {
+    /// Set the theme, contrast and scale that image variants are selected for, instead of those of the current user.
+    ///
+    /// See [DisplaySettings](struct.DisplaySettings.html) for how variants are named and selected.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use win_toast_notify::{WinToastNotify, CropCircle, DisplaySettings, Theme};
+    ///
+    /// // Shows logo.theme-dark.png if it exists next to logo.png
+    /// WinToastNotify::new()
+    ///     .set_logo(r"C:\Program Files\MyApp\logo.png", CropCircle::False)
+    ///     .set_display_settings(DisplaySettings { theme: Theme::Dark, ..Default::default() })
+    ///     .show()
+    ///     .expect("Failed to show toast notification");
+    /// ```
+    pub fn set_display_settings(mut self, display: DisplaySettings) -> Self {
+        self.display = Some(display);
+        self
+    }
+
+    /// Replace local images by their best variant. The display settings are only detected if there are variants.
+    pub(crate) fn resolve_variants(&mut self, options: &Options) {
+        let mut display = self.display;
+        for source in self.sources_mut() {
+            let MediaSource::File(path) = source else {
+                continue;
+            };
+            let candidates = variants(path);
+            if candidates.is_empty() {
+                continue;
+            }
+            let display = *display.get_or_insert_with(|| DisplaySettings::detect_with(options));
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            if let Some(variant) = display.select(&name, candidates.iter().map(String::as_str)) {
+                *path = path.with_file_name(variant);
+            }
+        }
+    }
+}
+
+/// The files a local image may be replaced by: its variant for `display`, or if not set, the variants for any
+/// display settings. Only the image itself if it has no variants.
+pub(crate) fn possible_variants(path: &Path, display: Option<DisplaySettings>) -> Vec<PathBuf> {
+    let candidates = variants(path);
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let possible: Vec<PathBuf> = match display {
+        Some(display) => display
+            .select(&name, candidates.iter().map(String::as_str))
+            .map(|variant| path.with_file_name(variant))
+            .into_iter()
+            .collect(),
+        None => {
+            let (stem, extension) = split_name(&name);
+            let any_settings = [Theme::Light, Theme::Dark].into_iter().flat_map(|theme| {
+                [Contrast::Standard, Contrast::High].map(|contrast| DisplaySettings { theme, contrast, scale: 100 })
+            });
+            let any_settings: Vec<_> = any_settings.collect();
+            candidates
+                .iter()
+                .filter(|candidate| any_settings.iter().any(|display| display.score(stem, extension, candidate).is_some()))
+                .map(|candidate| path.with_file_name(candidate))
+                .collect()
+        }
+    };
+    match possible.is_empty() {
+        true => vec![path.to_path_buf()],
+        false => possible,
+    }
+}
+
+/// The names of the files next to `path` that may be variants of it, including its own.
+fn variants(path: &Path) -> Vec<String> {
+    let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
+        return Vec::new();
+    };
+    let name = name.to_string_lossy();
+    let (stem, _) = split_name(&name);
+    let prefix = format!("{}.", stem.to_lowercase());
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let variants: Vec<String> = entries
+        .flatten()
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .filter(|candidate| candidate.to_lowercase().starts_with(&prefix))
+        .collect();
+    // Only the file itself is not worth selecting from
+    match variants.iter().any(|variant| *variant != name) {
+        true => variants,
+        false => Vec::new(),
+    }
+}
+
+/// Split a file name at its last `.` into stem and extension.
+fn split_name(name: &str) -> (&str, &str) {
+    name.rsplit_once('.').unwrap_or((name, ""))
+}
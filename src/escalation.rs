@@ -0,0 +1,270 @@
+use std::fmt::Write;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::image_cache::content_hash;
+use crate::{
+    audio, powershell, Action, ActivationType, Audio, Duration, Loop, Priority, Scenario, ToastNotifier, WinToastNotify,
+};
+
+/// Shows a notification again, more insistently, until it is acknowledged.
+///
+/// If the notification is not activated within `interval`, it is shown again with `Scenario::Alarm`, and from
+/// the third attempt with `Scenario::Urgent`, high priority and a looping alarm, up to `max_attempts` times in
+/// total. Activating the notification or any of its buttons acknowledges it; dismissing it does not.
+/// Notifications without buttons are given an "Acknowledge" button.
+///
+/// Escalation stops when the notification is hidden or removed by
+/// [ToastHandle::hide](struct.ToastHandle.html#method.hide), [ToastHandle::remove](struct.ToastHandle.html#method.remove)
+/// or [ToastNotifier::remove](struct.ToastNotifier.html#method.remove), when its group is removed by
+/// [remove_group](struct.ToastNotifier.html#method.remove_group), and when the notifications of the App ID are
+/// removed by [clear](struct.ToastNotifier.html#method.clear).
+///
+/// The notification is watched by a PowerShell process that keeps running until it is acknowledged or the last
+/// attempt is shown. Sound files and announcements are not played, and scheduled notifications, including
+/// deferred ones, are shown once. Notifications muted or downgraded during [quiet hours](struct.QuietHours.html)
+/// are not escalated.
+///
+/// # Examples
+/// ```
+/// use std::time::Duration;
+/// use win_toast_notify::{Audio, Escalation, Scenario, WinToastNotify};
+///
+/// let alert = WinToastNotify::new()
+///     .set_title("db-1 is down")
+///     .set_escalation(Escalation::new(Duration::from_secs(300), 3).set_audio(Audio::WinLoopingAlarm3));
+///
+/// assert!(matches!(alert.escalated(2).scenario, Scenario::Alarm));
+/// let last = alert.escalated(3);
+/// assert!(matches!(last.scenario, Scenario::Urgent));
+/// assert!(matches!(last.audio, Some(Audio::WinLoopingAlarm3)));
+/// assert_eq!(last.actions.unwrap()[0].action_content, "Acknowledge");
+/// ```
+#[derive(Debug, Clone)]
+pub struct Escalation {
+    /// How long to wait for the notification to be activated before showing it again.
+    pub interval: std::time::Duration,
+    /// How many times the notification is shown, including the first time.
+    pub max_attempts: u32,
+    /// The looping sound from the third attempt.
+    pub audio: Audio,
+}
+
+impl Escalation {
+    /// Escalate with `Audio::WinLoopingAlarm1`.
+    pub fn new(interval: std::time::Duration, max_attempts: u32) -> Self {
+        Self {
+            interval,
+            max_attempts: max_attempts.max(1),
+            audio: Audio::WinLoopingAlarm1,
+        }
+    }
+
+    pub fn set_audio(mut self, audio: Audio) -> Self {
+        self.audio = audio;
+        self
+    }
+}
+
+/// Defines `Wait-Acknowledged`, which waits until a time for the notification to be activated or hidden,
+/// to fail or to be stopped, and returns whether it was.
+///
+/// A stop file stops the escalation when it holds a time, in Unix milliseconds, from after `$Started`.
+const WAIT_ACKNOWLEDGED_FUNCTION: &str = r#"
+function Test-Stopped {
+    foreach ($File in $StopFiles) {
+        $Stopped = Get-Content -LiteralPath $File -Raw -ErrorAction SilentlyContinue
+        if ($Stopped -and [int64]"$Stopped".Trim() -ge $Started) { return $true }
+    }
+    return $false
+}
+function Wait-Acknowledged($Until) {
+    while ([DateTime]::UtcNow -lt $Until) {
+        if (Test-Stopped) { return $true }
+        $Next = Wait-Event -Timeout 1
+        if ($Next) {
+            $Next | Remove-Event
+            switch ($Next.SourceIdentifier) {
+                'ToastActivated' { return $true }
+                'ToastFailed' { return $true }
+                'ToastDismissed' { if ("$($Next.SourceArgs[1].Reason)" -eq 'ApplicationHidden') { return $true } }
+            }
+        }
+    }
+    return $false
+}
+"#;
+
+impl WinToastNotify {
+    /// Escalate the notification until it is acknowledged, see [Escalation](struct.Escalation.html).
+    pub fn set_escalation(mut self, escalation: Escalation) -> Self {
+        self.escalation = Some(escalation);
+        self
+    }
+
+    /// The notification shown at an attempt of its [Escalation](struct.Escalation.html), from 1.
+    pub fn escalated(&self, attempt: u32) -> WinToastNotify {
+        let mut toast = self.clone();
+        let Some(escalation) = &self.escalation else {
+            return toast;
+        };
+        if toast.actions.as_ref().map_or(true, Vec::is_empty) {
+            toast.actions = Some(vec![Action {
+                activation_type: ActivationType::Foreground,
+                action_content: String::from("Acknowledge"),
+                arguments: String::from("acknowledge"),
+                image_url: None,
+                button_style: None,
+            }]);
+        }
+        if attempt >= 2 {
+            toast.scenario = Scenario::Alarm;
+            toast.duration = Duration::Long;
+        }
+        if attempt >= 3 {
+            toast.scenario = Scenario::Urgent;
+            toast.priority = Priority::High;
+            toast.audio = Some(escalation.audio.clone());
+            toast.audio_loop = Loop::True;
+        }
+        toast
+    }
+
+    /// PowerShell that shows the attempts of the escalation with `$Notifier` until one is acknowledged.
+    ///
+    /// The script prints [audio::SHOWN] once the first attempt is shown, and stops when one of the stop files
+    /// of the notification is written.
+    pub(crate) fn escalation_script(
+        &self,
+        app_id: &str,
+        tag: &str,
+        group: Option<&str>,
+        options: &powershell::Options,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let Some(escalation) = &self.escalation else {
+            return Err("The notification has no escalation".into());
+        };
+        let stop_files: Vec<_> = stop_files(app_id, tag, group)
+            .iter()
+            .map(|path| powershell::quote(&path.to_string_lossy()))
+            .collect();
+        let mut script = format!(
+            "$Started = [DateTimeOffset]::UtcNow.ToUnixTimeMilliseconds()\n$StopFiles = @({})\n",
+            stop_files.join(", ")
+        );
+        script.push_str(WAIT_ACKNOWLEDGED_FUNCTION);
+        for attempt in 1..=escalation.max_attempts {
+            let toast = self.escalated(attempt);
+            script.push_str(&powershell::load_xml(&toast.to_xml_with(options)?));
+            script.push_str(&toast.notification_script(tag, group)?);
+            script.push_str(
+                r#"
+                Get-EventSubscriber | Unregister-Event
+                Get-Event | Remove-Event
+                $null = Register-ObjectEvent -InputObject $ToastNotification -EventName Activated -SourceIdentifier ToastActivated
+                $null = Register-ObjectEvent -InputObject $ToastNotification -EventName Dismissed -SourceIdentifier ToastDismissed
+                $null = Register-ObjectEvent -InputObject $ToastNotification -EventName Failed -SourceIdentifier ToastFailed
+                $Notifier.Show($ToastNotification)
+                "#,
+            );
+            if attempt == 1 {
+                writeln!(script, "[Console]::Out.WriteLine('{}')\n[Console]::Out.Flush()", audio::SHOWN)?;
+            }
+            if attempt < escalation.max_attempts {
+                writeln!(
+                    script,
+                    "if (Wait-Acknowledged ([DateTime]::UtcNow.AddSeconds({}))) {{ exit }}",
+                    escalation.interval.as_secs_f64()
+                )?;
+            }
+        }
+        Ok(script)
+    }
+}
+
+impl ToastNotifier {
+    /// Stop the escalations of the notification with this tag and group, of all notifications in the group if
+    /// `tag` is `None`, or of all notifications of the App ID if both are `None`.
+    ///
+    /// Called before the notifications are hidden or removed, so the watcher cannot show another attempt in
+    /// between.
+    pub(crate) fn stop_escalations(
+        &self,
+        tag: Option<&str>,
+        group: Option<&str>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let path = match (tag, group) {
+            (Some(tag), group) => tag_stop_file(&self.app_id, tag, group),
+            (None, Some(group)) => group_stop_file(&self.app_id, group),
+            (None, None) => app_stop_file(&self.app_id),
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
+        fs::write(path, now.to_string())?;
+        Ok(())
+    }
+}
+
+/// The files that stop the escalation of a notification when written: its own, its group's and its App ID's.
+fn stop_files(app_id: &str, tag: &str, group: Option<&str>) -> Vec<PathBuf> {
+    let mut files = vec![tag_stop_file(app_id, tag, group)];
+    if let Some(group) = group {
+        files.push(group_stop_file(app_id, group));
+    }
+    files.push(app_stop_file(app_id));
+    files
+}
+
+fn tag_stop_file(app_id: &str, tag: &str, group: Option<&str>) -> PathBuf {
+    stop_file(&["tag", app_id, tag, group.unwrap_or_default()])
+}
+
+fn group_stop_file(app_id: &str, group: &str) -> PathBuf {
+    stop_file(&["group", app_id, group])
+}
+
+fn app_stop_file(app_id: &str) -> PathBuf {
+    stop_file(&["app", app_id])
+}
+
+/// Stop files are named by a hash of their scope, which may hold any character.
+fn stop_file(scope: &[&str]) -> PathBuf {
+    std::env::temp_dir()
+        .join("win-toast-notify")
+        .join("escalation")
+        .join(format!("{}.stop", content_hash(scope.join("\0").as_bytes())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stops_by_scope() {
+        assert_eq!(
+            stop_files("app", "disk", Some("alerts")),
+            [tag_stop_file("app", "disk", Some("alerts")), group_stop_file("app", "alerts"), app_stop_file("app")]
+        );
+        assert_eq!(stop_files("app", "disk", None), [tag_stop_file("app", "disk", None), app_stop_file("app")]);
+        assert_ne!(tag_stop_file("app", "disk", None), tag_stop_file("other", "disk", None));
+        assert_ne!(tag_stop_file("app", "a", Some("b")), group_stop_file("app", "b"));
+    }
+
+    #[test]
+    fn script_watches_stop_files() {
+        let notifier = ToastNotifier::new(Some("app"));
+        let toast = WinToastNotify::new()
+            .set_title("db-1 is down")
+            .set_escalation(Escalation::new(std::time::Duration::from_secs(60), 2));
+        let script = toast
+            .escalation_script("app", "disk", Some("alerts"), &notifier.process_options())
+            .unwrap();
+        for file in stop_files("app", "disk", Some("alerts")) {
+            assert!(script.contains(&powershell::quote(&file.to_string_lossy())));
+        }
+        assert!(script.find("$Started").unwrap() < script.find("$Notifier.Show").unwrap());
+    }
+}
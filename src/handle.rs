@@ -0,0 +1,132 @@
+use std::fmt::Write;
+
+use crate::{powershell, Delivery, ToastNotifier, WinToastNotify};
+
+/// A notification that has been shown, returned by [WinToastNotify::show](struct.WinToastNotify.html#method.show).
+///
+/// Notifications without a tag are given a generated one, so the handle can always refer to the notification.
+///
+/// With [remove_on_drop](#method.remove_on_drop), dropping the handle **blocks** while PowerShell removes the
+/// notification, up to the [timeout](struct.ToastNotifier.html#method.set_timeout) of the notifier, 30 seconds by
+/// default. Drop it where blocking is acceptable, not on a UI thread or an async executor.
+///
+/// # Examples
+/// ```
+/// use win_toast_notify::WinToastNotify;
+///
+/// let working = WinToastNotify::new()
+///     .set_title("Working…")
+///     .set_messages(vec!["Exporting the report"])
+///     .show()
+///     .expect("Failed to show toast notification")
+///     .remove_on_drop();
+///
+/// // ... export the report ...
+///
+/// working
+///     .replace(&WinToastNotify::new().set_title("Working…").set_messages(vec!["Uploading the report"]))
+///     .expect("Failed to replace toast notification");
+///
+/// // ... upload the report ...
+///
+/// // The notice is removed, the result is a notification of its own
+/// drop(working);
+/// WinToastNotify::new()
+///     .set_title("Export finished")
+///     .show()
+///     .expect("Failed to show toast notification");
+/// ```
+pub struct ToastHandle {
+    notifier: ToastNotifier,
+    tag: String,
+    group: Option<String>,
+    remove_on_drop: bool,
+}
+
+impl ToastHandle {
+    pub(crate) fn new(notifier: ToastNotifier, tag: String, group: Option<String>) -> Self {
+        Self {
+            notifier,
+            tag,
+            group,
+            remove_on_drop: false,
+        }
+    }
+
+    pub fn tag(&self) -> &str {
+        &self.tag
+    }
+
+    pub fn group(&self) -> Option<&str> {
+        self.group.as_deref()
+    }
+
+    pub fn notifier(&self) -> &ToastNotifier {
+        &self.notifier
+    }
+
+    /// Remove the notification when the handle is dropped.
+    ///
+    /// Useful for transient notices that must disappear when an operation ends. Dropping the handle then runs
+    /// PowerShell and blocks until it is done or times out, errors are ignored.
+    pub fn remove_on_drop(mut self) -> Self {
+        self.remove_on_drop = true;
+        self
+    }
+
+    /// Hide the notification from the screen, which stops its [escalation](struct.Escalation.html).
+    pub fn hide(&self) -> Result<(), Box<dyn std::error::Error>> {
+        self.notifier.stop_escalations(Some(&self.tag), self.group.as_deref())?;
+        let mut command = self.notifier.notifier_script();
+        write!(
+            command,
+            r#"
+            $History = [Windows.UI.Notifications.ToastNotificationManager, Windows.UI.Notifications, ContentType = WindowsRuntime]::History
+            foreach ($Toast in $History.GetHistory($AppId)) {{
+                if ($Toast.Tag -eq {} -and $Toast.Group -eq {}) {{
+                    $Notifier.Hide($Toast)
+                }}
+            }}
+            "#,
+            powershell::quote(&self.tag),
+            powershell::quote(self.group.as_deref().unwrap_or_default())
+        )?;
+        self.notifier.run(&command)?;
+        Ok(())
+    }
+
+    /// Remove the notification from the screen and Action Center, which stops its
+    /// [escalation](struct.Escalation.html).
+    pub fn remove(&self) -> Result<(), Box<dyn std::error::Error>> {
+        self.notifier.remove(&self.tag, self.group.as_deref())
+    }
+
+    /// Replace the notification with new content, keeping its tag and group.
+    ///
+    /// The new content is sent as by [ToastNotifier::send](struct.ToastNotifier.html#method.send), so channels,
+    /// quiet hours, the rate limit and the digest apply to it.
+    pub fn replace(&self, toast: &WinToastNotify) -> Result<Delivery, Box<dyn std::error::Error>> {
+        let mut toast = toast.clone();
+        toast.tag = Some(self.tag.clone());
+        toast.group = self.group.clone();
+        if let Some(progress) = &mut toast.progress {
+            progress.tag = self.tag.clone();
+        }
+        self.notifier.send(&toast)
+    }
+
+    /// Whether the notification is still on the screen or in Action Center.
+    pub fn is_present(&self) -> Result<bool, Box<dyn std::error::Error>> {
+        Ok(self.notifier.history()?.iter().any(|toast| {
+            toast.tag.as_deref() == Some(self.tag.as_str()) && toast.group == self.group
+        }))
+    }
+}
+
+impl Drop for ToastHandle {
+    fn drop(&mut self) {
+        if self.remove_on_drop {
+            let _ = self.remove();
+        }
+    }
+}
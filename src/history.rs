@@ -0,0 +1,118 @@
+use std::fmt::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::notifier::{content_texts, non_empty};
+use crate::{powershell, ToastNotifier};
+
+const HISTORY: &str = "$History = [Windows.UI.Notifications.ToastNotificationManager, Windows.UI.Notifications, ContentType = WindowsRuntime]::History\n";
+
+impl ToastNotifier {
+    /// List the notifications of this App ID that are in Action Center.
+    ///
+    /// # Examples
+    /// ```
+    /// use win_toast_notify::ToastNotifier;
+    ///
+    /// let notifier = ToastNotifier::new(None);
+    /// for toast in notifier.history().expect("Failed to get notification history") {
+    ///     println!("{:?}/{:?}: {:?}", toast.group, toast.tag, toast.title);
+    /// }
+    ///
+    /// // The build is green again, remove the failure notifications
+    /// notifier.remove_group("build-failures").expect("Failed to remove notifications");
+    /// ```
+    pub fn history(&self) -> Result<Vec<DeliveredToast>, Box<dyn std::error::Error>> {
+        let mut command = String::from(powershell::ENCODE_FUNCTION);
+        command.push_str(&self.notifier_script());
+        command.push_str(HISTORY);
+        command.push_str(
+            r#"
+            foreach ($Toast in $History.GetHistory($AppId)) {
+                $Expiration = if ($null -ne $Toast.ExpirationTime) { $Toast.ExpirationTime.ToUnixTimeMilliseconds() } else { '' }
+                @(
+                    (Encode $Toast.Tag),
+                    (Encode $Toast.Group),
+                    $Expiration,
+                    (Encode $Toast.Content.GetXml())
+                ) -join "`t"
+            }
+            "#,
+        );
+        self.run(&command)?
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(DeliveredToast::parse)
+            .collect()
+    }
+
+    /// Remove the notification with this tag and group from Action Center, and stop its escalation.
+    pub fn remove(&self, tag: &str, group: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+        self.stop_escalations(Some(tag), group)?;
+        let mut command = self.notifier_script();
+        command.push_str(HISTORY);
+        writeln!(
+            command,
+            "$History.Remove({}, {}, $AppId)",
+            powershell::quote(tag),
+            powershell::quote(group.unwrap_or_default())
+        )?;
+        self.run(&command)?;
+        Ok(())
+    }
+
+    /// Remove all notifications of a group from Action Center, and stop their escalations.
+    pub fn remove_group(&self, group: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.stop_escalations(None, Some(group))?;
+        let mut command = self.notifier_script();
+        command.push_str(HISTORY);
+        writeln!(command, "$History.RemoveGroup({}, $AppId)", powershell::quote(group))?;
+        self.run(&command)?;
+        Ok(())
+    }
+
+    /// Remove all notifications of this App ID from Action Center, and stop their escalations.
+    pub fn clear(&self) -> Result<(), Box<dyn std::error::Error>> {
+        self.stop_escalations(None, None)?;
+        let mut command = self.notifier_script();
+        command.push_str(HISTORY);
+        command.push_str("$History.Clear($AppId)\n");
+        self.run(&command)?;
+        Ok(())
+    }
+}
+
+/// A notification in Action Center, as returned by [ToastNotifier::history](struct.ToastNotifier.html#method.history).
+#[derive(Debug, Clone)]
+pub struct DeliveredToast {
+    pub tag: Option<String>,
+    pub group: Option<String>,
+    pub expiration_time: Option<SystemTime>,
+    /// The first text element of the notification.
+    pub title: Option<String>,
+    /// The remaining text elements of the notification.
+    pub messages: Vec<String>,
+    /// The notification XML.
+    pub content: String,
+}
+
+impl DeliveredToast {
+    fn parse(line: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let fields: Vec<&str> = line.trim().split('\t').collect();
+        let [tag, group, expiration_time, content] = fields[..] else {
+            return Err(format!("Unexpected notification history output: \"{}\"", line).into());
+        };
+        let content = powershell::decode(content)?;
+        let mut texts = content_texts(&content).into_iter();
+        Ok(Self {
+            tag: non_empty(powershell::decode(tag)?),
+            group: non_empty(powershell::decode(group)?),
+            expiration_time: match expiration_time {
+                "" => None,
+                millis => Some(UNIX_EPOCH + std::time::Duration::from_millis(millis.parse()?)),
+            },
+            title: texts.next(),
+            messages: texts.collect(),
+            content,
+        })
+    }
+}
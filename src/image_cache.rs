@@ -0,0 +1,420 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use sha2::{Digest, Sha256};
+
+use crate::image_info::ImageFormat;
+#[cfg(feature = "image")]
+use crate::normalize::{self, LogoCrop};
+use crate::{notifier, powershell, MediaSource, WinToastNotify, MAX_IMAGE_BYTES};
+
+/// Stores images in a local directory before notifications are rendered.
+///
+/// Images set from bytes are always written to a cache, the default one unless the
+/// [ToastNotifier](struct.ToastNotifier.html) has its own.
+///
+/// Windows does not load `http(s)` images for unpackaged apps, such as the default PowerShell App ID.
+/// With a cache set on the notifier, remote images are also downloaded and the notification
+/// refers to the local file instead.
+///
+/// Files are named by content hash, so the same image from different sources is stored once.
+/// Files older than the maximum age are removed, and files are renewed each time they are used.
+///
+/// With the `image` feature, the cache can also scale images down to the limits of Windows,
+/// convert formats Windows does not render such as WebP to PNG, and crop logos, see
+/// [set_normalize](#method.set_normalize).
+///
+/// # Examples
+/// ```
+/// use std::time::Duration;
+/// use win_toast_notify::{ImageCache, ImagePlacement, ToastNotifier, WinToastNotify};
+///
+/// let notifier = ToastNotifier::new(None)
+///     .set_image_cache(ImageCache::default().set_max_age(Duration::from_secs(24 * 60 * 60)));
+///
+/// let toast = WinToastNotify::new()
+///     .set_title("Now playing")
+///     .set_image("https://www.rust-lang.org/static/images/rust-social-wide.jpg", ImagePlacement::Top);
+/// notifier.show(&toast).expect("Failed to show toast notification");
+/// ```
+pub struct ImageCache {
+    dir: PathBuf,
+    max_bytes: u64,
+    max_age: Duration,
+    /// Downloads with PowerShell if not set.
+    fetcher: Option<Box<dyn ImageFetcher>>,
+    #[cfg(feature = "image")]
+    normalize: bool,
+    #[cfg(feature = "image")]
+    logo_crop: LogoCrop,
+}
+
+/// Downloads images for an [ImageCache](struct.ImageCache.html).
+///
+/// The default fetcher uses PowerShell. Implement this trait to use another HTTP client.
+pub trait ImageFetcher: Send + Sync {
+    /// Download `url`, failing if the response is larger than `max_bytes`.
+    fn fetch(&self, url: &str, max_bytes: u64) -> Result<FetchedImage, Box<dyn std::error::Error>>;
+}
+
+/// A downloaded image.
+pub struct FetchedImage {
+    /// The `Content-Type` of the response.
+    pub content_type: Option<String>,
+    pub bytes: Vec<u8>,
+}
+
+impl Default for ImageCache {
+    /// A cache in `%TEMP%\win-toast-notify`.
+    fn default() -> Self {
+        Self::new(std::env::temp_dir().join("win-toast-notify"))
+    }
+}
+
+impl ImageCache {
+    /// A cache in the given directory, which is created when needed.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            max_bytes: MAX_IMAGE_BYTES,
+            max_age: Duration::from_secs(7 * 24 * 60 * 60),
+            fetcher: None,
+            #[cfg(feature = "image")]
+            normalize: false,
+            #[cfg(feature = "image")]
+            logo_crop: LogoCrop::None,
+        }
+    }
+
+    /// Maximum size of a downloaded image, 3 MB by default.
+    pub fn set_max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    /// How long files are kept, 7 days by default.
+    pub fn set_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = max_age;
+        self
+    }
+
+    pub fn set_fetcher(mut self, fetcher: impl ImageFetcher + 'static) -> Self {
+        self.fetcher = Some(Box::new(fetcher));
+        self
+    }
+
+    /// Scale local images down to `MAX_IMAGE_DIMENSION` and `MAX_IMAGE_BYTES`, and convert formats
+    /// Windows does not render to PNG, before notifications are shown. Disabled by default.
+    ///
+    /// Windows drops images over its limits without any error. Images that are already fine are used
+    /// as they are, converted images are stored in the cache directory.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use win_toast_notify::{ImageCache, ImagePlacement, LogoCrop, ToastNotifier, WinToastNotify, CropCircle};
+    ///
+    /// let notifier = ToastNotifier::new(None)
+    ///     .set_image_cache(ImageCache::default().set_normalize(true).set_logo_crop(LogoCrop::Circle));
+    ///
+    /// let toast = WinToastNotify::new()
+    ///     .set_title("Build finished")
+    ///     .set_logo(r"C:\ci\artifacts\avatar.webp", CropCircle::True)
+    ///     .set_image(r"C:\ci\artifacts\coverage.png", ImagePlacement::Top);
+    /// notifier.show(&toast).expect("Failed to show toast notification");
+    /// ```
+    #[cfg(feature = "image")]
+    pub fn set_normalize(mut self, normalize: bool) -> Self {
+        self.normalize = normalize;
+        self
+    }
+
+    /// Crop the logo before notifications are shown, `LogoCrop::None` by default.
+    ///
+    /// The cropped logo is also scaled down and converted as by [set_normalize](#method.set_normalize).
+    #[cfg(feature = "image")]
+    pub fn set_logo_crop(mut self, crop: LogoCrop) -> Self {
+        self.logo_crop = crop;
+        self
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Return a local source for `source`, downloading it if it is remote and writing it if it is data.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::{Read, Write};
+    /// use std::net::TcpListener;
+    /// use win_toast_notify::{ImageCache, MediaSource};
+    ///
+    /// // Serve a 1x1 GIF on a local port
+    /// let image: &[u8] = b"GIF89a\x01\x00\x01\x00\x80\x00\x00\xff\xff\xff\x00\x00\x00,\x00\x00\x00\x00\x01\x00\x01\x00\x00\x02\x02D\x01\x00;";
+    /// let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    /// let url = format!("http://{}/pixel.gif", listener.local_addr().unwrap());
+    /// std::thread::spawn(move || {
+    ///     for mut stream in listener.incoming().flatten() {
+    ///         let _ = stream.read(&mut [0; 4096]);
+    ///         let _ = write!(
+    ///             stream,
+    ///             "HTTP/1.1 200 OK\r\nContent-Type: image/gif\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+    ///             image.len()
+    ///         );
+    ///         let _ = stream.write_all(image);
+    ///     }
+    /// });
+    ///
+    /// let cache = ImageCache::new(std::env::temp_dir().join("win-toast-notify-example"));
+    /// let local = cache.localize(&MediaSource::Http(url.clone())).expect("Failed to download the image");
+    /// assert_eq!(std::fs::read(local.as_path().unwrap()).unwrap(), image);
+    ///
+    /// // The cached file is used from now on
+    /// assert_eq!(cache.localize(&MediaSource::Http(url)).unwrap(), local);
+    /// ```
+    pub fn localize(&self, source: &MediaSource) -> Result<MediaSource, Box<dyn std::error::Error>> {
+        self.localize_with(source, &powershell::Options::default())
+    }
+
+    /// Return a local source for `source`, downloading with the timeout and cancellation of a notifier.
+    fn localize_with(
+        &self,
+        source: &MediaSource,
+        options: &powershell::Options,
+    ) -> Result<MediaSource, Box<dyn std::error::Error>> {
+        let url = match source {
+            MediaSource::Http(url) => url,
+            MediaSource::Bytes(bytes) => return Ok(MediaSource::File(self.store(bytes)?)),
+            _ => return Ok(source.clone()),
+        };
+        fs::create_dir_all(&self.dir)?;
+        self.prune()?;
+
+        // The URL index points to the file holding its content
+        let index = self.dir.join(format!("url-{}", content_hash(url.as_bytes())));
+        if let Some(path) = lookup(&index)? {
+            return Ok(MediaSource::File(path));
+        }
+
+        let image = match &self.fetcher {
+            Some(fetcher) => fetcher.fetch(url, self.max_bytes)?,
+            None => fetch(url, self.max_bytes, options)?,
+        };
+        if image.bytes.len() as u64 > self.max_bytes {
+            return Err(format!(
+                "Image \"{}\" is {} bytes, the limit is {}",
+                url,
+                image.bytes.len(),
+                self.max_bytes
+            )
+            .into());
+        }
+        if let Some(content_type) = &image.content_type {
+            if !content_type.trim().to_ascii_lowercase().starts_with("image/") {
+                return Err(format!("\"{}\" is not an image, but {}", url, content_type).into());
+            }
+        }
+        let path = self.store(&image.bytes)?;
+        write_index(&index, &path)?;
+        Ok(MediaSource::File(path))
+    }
+
+    /// Return a source for the normalized version of a local image, cropped to `crop`.
+    #[cfg(feature = "image")]
+    fn normalize(&self, source: &MediaSource, crop: LogoCrop) -> Result<MediaSource, Box<dyn std::error::Error>> {
+        let Some(path) = source.as_path() else {
+            return Ok(source.clone());
+        };
+        let bytes = fs::read(path)?;
+
+        // The index points from the original content to the normalized file
+        let key = format!("{}-{:?}", content_hash(&bytes), crop);
+        let index = self.dir.join(format!("normalized-{}", content_hash(key.as_bytes())));
+        if let Some(path) = lookup(&index)? {
+            return Ok(MediaSource::File(path));
+        }
+
+        let normalized = normalize::normalize(&bytes, crop)
+            .map_err(|e| format!("Failed to normalize image \"{}\": {}", path.display(), e))?;
+        match normalized {
+            Some(normalized) => {
+                let path = self.store(&normalized)?;
+                write_index(&index, &path)?;
+                Ok(MediaSource::File(path))
+            }
+            None => Ok(source.clone()),
+        }
+    }
+
+    /// Store image bytes under their content hash, returning the path of the file.
+    pub(crate) fn store(&self, bytes: &[u8]) -> Result<PathBuf, Box<dyn std::error::Error>> {
+        ImageFormat::sniff(bytes).ok_or("The data is not a supported image")?;
+        fs::create_dir_all(&self.dir)?;
+        self.prune()?;
+        let path = self.path_for(bytes);
+        if path.is_file() {
+            renew(&path)?;
+        } else {
+            // Another writer may store the same image meanwhile
+            let partial = path.with_extension(format!("{}.part", notifier::unique_id()));
+            fs::write(&partial, bytes)?;
+            if let Err(e) = fs::rename(&partial, &path) {
+                let _ = fs::remove_file(&partial);
+                // The file has the same content whoever wrote it
+                if !path.is_file() {
+                    return Err(e.into());
+                }
+            }
+        }
+        Ok(path)
+    }
+
+    /// The path image bytes are stored at.
+    pub(crate) fn path_for(&self, bytes: &[u8]) -> PathBuf {
+        let extension = ImageFormat::sniff(bytes).map_or("bin", |format| format.extension());
+        self.dir.join(format!("{}.{}", content_hash(bytes), extension))
+    }
+
+    /// Remove files older than the maximum age.
+    pub fn prune(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return Ok(());
+        };
+        let now = SystemTime::now();
+        for entry in entries.flatten() {
+            let modified = entry.metadata().and_then(|metadata| metadata.modified());
+            let expired = modified
+                .ok()
+                .and_then(|modified| now.duration_since(modified).ok())
+                .is_some_and(|age| age > self.max_age);
+            if expired {
+                let _ = fs::remove_file(entry.path());
+            }
+        }
+        Ok(())
+    }
+
+    /// Replace the images of a notification set from bytes, and remote images if `download` is set, by local files.
+    pub(crate) fn localize_toast(
+        &self,
+        toast: &mut WinToastNotify,
+        download: bool,
+        options: &powershell::Options,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        for source in toast.sources_mut() {
+            if download || matches!(source, MediaSource::Bytes(_)) {
+                *source = self.localize_with(source, options)?;
+            }
+        }
+        #[cfg(feature = "image")]
+        {
+            let has_logo = toast.logo.is_some();
+            for (i, source) in toast.sources_mut().enumerate() {
+                let crop = if has_logo && i == 0 { self.logo_crop } else { LogoCrop::None };
+                if self.normalize || crop != LogoCrop::None {
+                    *source = self.normalize(source, crop)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Download with `HttpWebRequest` in PowerShell, unless the cache has its own fetcher.
+fn fetch(url: &str, max_bytes: u64, options: &powershell::Options) -> Result<FetchedImage, Box<dyn std::error::Error>> {
+    let file = std::env::temp_dir().join(format!("win-toast-notify-{}.part", notifier::unique_id()));
+    let command = format!(
+        r#"
+        $ErrorActionPreference = 'Stop'
+        $MaxBytes = {}
+        $Response = [System.Net.WebRequest]::Create({}).GetResponse()
+        try {{
+            if ($Response.ContentLength -gt $MaxBytes) {{
+                throw "The image is $($Response.ContentLength) bytes, the limit is $MaxBytes"
+            }}
+            $Stream = $Response.GetResponseStream()
+            $File = [System.IO.File]::Create({})
+            try {{
+                $Buffer = New-Object byte[] 65536
+                $Total = 0
+                while (($Read = $Stream.Read($Buffer, 0, $Buffer.Length)) -gt 0) {{
+                    $Total += $Read
+                    if ($Total -gt $MaxBytes) {{ throw "The image is larger than $MaxBytes bytes" }}
+                    $File.Write($Buffer, 0, $Read)
+                }}
+            }} finally {{
+                $File.Dispose()
+            }}
+            $Response.ContentType
+        }} finally {{
+            $Response.Dispose()
+        }}
+        "#,
+        max_bytes,
+        powershell::quote(url),
+        powershell::quote(&file.to_string_lossy())
+    );
+    let result = powershell::run_with(&command, options).and_then(|content_type| {
+        Ok(FetchedImage {
+            content_type: Some(content_type.trim().to_string()).filter(|s| !s.is_empty()),
+            bytes: fs::read(&file)?,
+        })
+    });
+    let _ = fs::remove_file(&file);
+    result
+}
+
+/// The existing file an index file points to.
+fn lookup(index: &Path) -> std::io::Result<Option<PathBuf>> {
+    let (Ok(name), Some(dir)) = (fs::read_to_string(index), index.parent()) else {
+        return Ok(None);
+    };
+    let path = dir.join(name.trim());
+    if !path.is_file() {
+        return Ok(None);
+    }
+    renew(&path)?;
+    Ok(Some(path))
+}
+
+fn write_index(index: &Path, path: &Path) -> std::io::Result<()> {
+    fs::write(index, path.file_name().unwrap_or_default().to_string_lossy().as_bytes())
+}
+
+/// Update the modification time of a file, so it is not pruned while in use.
+fn renew(path: &Path) -> std::io::Result<()> {
+    fs::File::options().write(true).open(path)?.set_modified(SystemTime::now())
+}
+
+/// SHA-256 hash of the bytes, as 64 hex digits.
+///
+/// Downloaded content is named by its hash, so the hash must be collision-resistant: otherwise a crafted image
+/// could take the place of another one in the cache.
+pub(crate) fn content_hash(bytes: &[u8]) -> String {
+    Sha256::digest(bytes).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn stores_concurrently() {
+        let dir = std::env::temp_dir().join(format!("win-toast-notify-test-{}", notifier::unique_id()));
+        let cache = ImageCache::new(&dir);
+        let image: &[u8] = b"GIF89a\x01\x00\x01\x00\x80\x00\x00\xff\xff\xff\x00\x00\x00,\x00\x00\x00\x00\x01\x00\x01\x00\x00\x02\x02D\x01\x00;";
+
+        let paths: Vec<_> = thread::scope(|scope| {
+            let writers: Vec<_> = (0..8).map(|_| scope.spawn(|| cache.store(image).unwrap())).collect();
+            writers.into_iter().map(|writer| writer.join().unwrap()).collect()
+        });
+        assert!(paths.iter().all(|path| *path == cache.path_for(image)));
+        assert_eq!(fs::read(&paths[0]).unwrap(), image);
+        // No partial files are left behind
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
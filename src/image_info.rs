@@ -0,0 +1,85 @@
+/// Image formats recognized from their leading bytes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum ImageFormat {
+    Png,
+    Jpeg,
+    Gif,
+    Bmp,
+    WebP,
+}
+
+impl ImageFormat {
+    pub(crate) fn sniff(bytes: &[u8]) -> Option<Self> {
+        if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Some(ImageFormat::Png)
+        } else if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+            Some(ImageFormat::Jpeg)
+        } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+            Some(ImageFormat::Gif)
+        } else if bytes.starts_with(b"BM") {
+            Some(ImageFormat::Bmp)
+        } else if bytes.len() >= 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+            Some(ImageFormat::WebP)
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Jpeg => "jpg",
+            ImageFormat::Gif => "gif",
+            ImageFormat::Bmp => "bmp",
+            ImageFormat::WebP => "webp",
+        }
+    }
+}
+
+/// Width and height of an image, read from its header.
+pub(crate) fn dimensions(bytes: &[u8]) -> Option<(u32, u32)> {
+    let be16 = |i: usize| Some(u16::from_be_bytes(bytes.get(i..i + 2)?.try_into().ok()?) as u32);
+    let le16 = |i: usize| Some(u16::from_le_bytes(bytes.get(i..i + 2)?.try_into().ok()?) as u32);
+    let be32 = |i: usize| Some(u32::from_be_bytes(bytes.get(i..i + 4)?.try_into().ok()?));
+    let le32 = |i: usize| Some(i32::from_le_bytes(bytes.get(i..i + 4)?.try_into().ok()?).unsigned_abs());
+    let le24 = |i: usize| {
+        let b = bytes.get(i..i + 3)?;
+        Some(b[0] as u32 | (b[1] as u32) << 8 | (b[2] as u32) << 16)
+    };
+
+    match ImageFormat::sniff(bytes)? {
+        ImageFormat::Png => Some((be32(16)?, be32(20)?)),
+        ImageFormat::Gif => Some((le16(6)?, le16(8)?)),
+        ImageFormat::Bmp => Some((le32(18)?, le32(22)?)),
+        ImageFormat::WebP => match bytes.get(12..16)? {
+            b"VP8X" => Some((le24(24)? + 1, le24(27)? + 1)),
+            b"VP8 " => Some((le16(26)? & 0x3FFF, le16(28)? & 0x3FFF)),
+            b"VP8L" => {
+                let bits = u32::from_le_bytes(bytes.get(21..25)?.try_into().ok()?);
+                Some(((bits & 0x3FFF) + 1, ((bits >> 14) & 0x3FFF) + 1))
+            }
+            _ => None,
+        },
+        ImageFormat::Jpeg => {
+            // Walk the segments until a start of frame marker
+            let mut i = 2;
+            loop {
+                while *bytes.get(i)? != 0xFF {
+                    i += 1;
+                }
+                while *bytes.get(i)? == 0xFF {
+                    i += 1;
+                }
+                let marker = *bytes.get(i)?;
+                i += 1;
+                match marker {
+                    0xD8 | 0x01 | 0xD0..=0xD7 => continue,
+                    0xC0..=0xCF if !matches!(marker, 0xC4 | 0xC8 | 0xCC) => {
+                        return Some((be16(i + 5)?, be16(i + 3)?));
+                    }
+                    _ => i += be16(i)? as usize,
+                }
+            }
+        }
+    }
+}
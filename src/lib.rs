@@ -1,8 +1,55 @@
+use std::borrow::Cow;
 use std::fmt::Write;
-use std::os::windows::process::CommandExt;
-use std::process::Command;
+use std::time::SystemTime;
 use xml::escape::escape_str_attribute;
 
+mod announce;
+mod arguments;
+mod audio;
+mod badge;
+mod channel;
+mod digest;
+mod display;
+mod escalation;
+mod handle;
+mod history;
+mod image_cache;
+mod image_info;
+mod media;
+mod middleware;
+#[cfg(feature = "image")]
+mod normalize;
+mod notifier;
+mod platform;
+mod powershell;
+mod queue;
+mod quiet;
+mod rate_limit;
+mod validate;
+
+pub use announce::{Announce, AnnounceMode};
+pub use arguments::ToastArguments;
+pub use badge::{Badge, BadgeGlyph};
+pub use channel::{Channel, Channels};
+pub use digest::Digest;
+pub use display::{Contrast, DisplaySettings, Theme};
+pub use escalation::Escalation;
+pub use handle::ToastHandle;
+pub use history::DeliveredToast;
+pub use image_cache::{FetchedImage, ImageCache, ImageFetcher};
+pub use media::MediaSource;
+pub use middleware::{Middleware, Operation};
+#[cfg(feature = "image")]
+pub use normalize::LogoCrop;
+pub use notifier::{Delivery, ScheduleOptions, ScheduledToast, ToastNotifier};
+pub use platform::{Capability, TargetPlatform};
+pub use powershell::{CancelHandle, ProcessError};
+pub use queue::ToastQueue;
+pub use quiet::{Clock, QuietAction, QuietHours, QuietWindow, TimeZone, Weekday};
+pub use rate_limit::{Overflow, RateDecision, RateLimit, SuppressReason};
+pub use validate::{Diagnostic, Severity, MAX_ACTIONS, MAX_IMAGE_BYTES, MAX_IMAGE_DIMENSION, MAX_TEXTS};
+
+#[derive(Debug, Clone)]
 pub struct WinToastNotify {
     pub app_id: Option<String>,
     pub duration: Duration,
@@ -10,14 +57,32 @@ pub struct WinToastNotify {
     pub open: Option<String>,
     pub title: Option<String>,
     pub messages: Option<Vec<String>>,
-    pub logo: Option<String>,
+    pub logo: Option<MediaSource>,
     pub logo_circle: CropCircle,
-    pub image: Option<String>,
+    pub logo_alt: Option<String>,
+    pub image: Option<MediaSource>,
     pub image_placement: ImagePlacement,
+    pub image_alt: Option<String>,
+    pub images: Option<Vec<InlineImage>>,
     pub actions: Option<Vec<Action>>,
     pub progress: Option<Progress>,
+    pub header: Option<Header>,
     pub audio: Option<Audio>,
     pub audio_loop: Loop,
+    pub expiration_time: Option<SystemTime>,
+    pub expires_on_reboot: bool,
+    pub priority: Priority,
+    pub suppress_popup: bool,
+    pub mirroring: NotificationMirroring,
+    pub remote_id: Option<String>,
+    pub tag: Option<String>,
+    pub group: Option<String>,
+    pub platform: Option<TargetPlatform>,
+    pub display: Option<DisplaySettings>,
+    pub announce: Option<Announce>,
+    pub bypass_quiet_hours: bool,
+    pub channel: Option<String>,
+    pub escalation: Option<Escalation>,
 }
 
 impl Default for WinToastNotify {
@@ -37,12 +102,30 @@ impl WinToastNotify {
             messages: None,
             logo: None,
             logo_circle: CropCircle::False,
+            logo_alt: None,
             image: None,
             image_placement: ImagePlacement::Top,
+            image_alt: None,
+            images: None,
             actions: None,
             audio: Some(Audio::WinDefault),
             audio_loop: Loop::False,
             progress: None,
+            header: None,
+            expiration_time: None,
+            expires_on_reboot: false,
+            priority: Priority::Default,
+            suppress_popup: false,
+            mirroring: NotificationMirroring::Allowed,
+            remote_id: None,
+            tag: None,
+            group: None,
+            platform: None,
+            display: None,
+            announce: None,
+            bypass_quiet_hours: false,
+            channel: None,
+            escalation: None,
         }
     }
 
@@ -84,7 +167,7 @@ impl WinToastNotify {
     /// 
     /// - Name: `IconUrl`, Value: Path to your APP icon
     pub fn set_app_id(mut self, id: &str) -> Self {
-        self.app_id = Some(escape_str_attribute(id).into_owned());
+        self.app_id = Some(id.to_string());
         self
     }
 
@@ -100,7 +183,7 @@ impl WinToastNotify {
         self
     }
 
-    /// Open link when notification is clicked.
+    /// Open link when notification is clicked. Environment variables such as `$env:USERPROFILE` are expanded.
     /// # Examples
     /// ```
     /// use win_toast_notify::WinToastNotify;
@@ -112,13 +195,13 @@ impl WinToastNotify {
     ///     .expect("Failed to show toast notification");
     /// ```
     pub fn set_open(mut self, url_or_path: &str) -> Self {
-        self.open = Some(escape_str_attribute(url_or_path.trim()).into_owned());
+        self.open = Some(url_or_path.trim().to_string());
         self
     }
 
     /// Set the title of the notification.
     pub fn set_title(mut self, title: &str) -> Self {
-        self.title = Some(escape_str_attribute(title).into_owned());
+        self.title = Some(title.to_string());
         self
     }
 
@@ -140,24 +223,77 @@ impl WinToastNotify {
     ///     .expect("Failed to show toast notification");
     /// ```
     pub fn set_messages(mut self, messages: Vec<&str>) -> Self {
-        self.messages = Some(Box::new(messages.iter().map(|t| escape_str_attribute(t).into_owned())).collect());
+        self.messages = Some(messages.iter().map(|t| t.to_string()).collect());
         self
     }
 
     /// Set the notification logo and specify whether to crop it into a circle.
-    pub fn set_logo(mut self, path: &str, hint_crop: CropCircle) -> Self {
-        self.logo = Some(escape_str_attribute(path.trim()).into_owned());
+    ///
+    /// Accepts paths and URIs, see [MediaSource](enum.MediaSource.html).
+    pub fn set_logo(mut self, source: impl Into<MediaSource>, hint_crop: CropCircle) -> Self {
+        self.logo = Some(source.into());
         self.logo_circle = hint_crop;
         self
     }
 
+    /// Set the notification logo from image data, such as a generated avatar.
+    ///
+    /// The data is written to a file named by its hash in the [ImageCache](struct.ImageCache.html) when the notification is shown.
+    pub fn set_logo_bytes(self, bytes: &[u8], hint_crop: CropCircle) -> Self {
+        self.set_logo(MediaSource::from(bytes), hint_crop)
+    }
+
     /// Set the notification image and its position.
-    pub fn set_image(mut self, path: &str, position: ImagePlacement) -> Self {
-        self.image = Some(escape_str_attribute(path.trim()).into_owned());
+    ///
+    /// Accepts paths and URIs, see [MediaSource](enum.MediaSource.html).
+    pub fn set_image(mut self, source: impl Into<MediaSource>, position: ImagePlacement) -> Self {
+        self.image = Some(source.into());
         self.image_placement = position;
         self
     }
 
+    /// Set the notification image from image data, such as a screenshot or chart.
+    ///
+    /// The data is written to a file named by its hash in the [ImageCache](struct.ImageCache.html) when the notification is shown.
+    pub fn set_image_bytes(self, bytes: &[u8], position: ImagePlacement) -> Self {
+        self.set_image(MediaSource::from(bytes), position)
+    }
+
+    /// Set the text read by screen readers for the logo.
+    pub fn set_logo_alt(mut self, alt: &str) -> Self {
+        self.logo_alt = Some(alt.to_string());
+        self
+    }
+
+    /// Set the text read by screen readers for the image set by `set_image`.
+    pub fn set_image_alt(mut self, alt: &str) -> Self {
+        self.image_alt = Some(alt.to_string());
+        self
+    }
+
+    /// Set images shown inline below the messages, after the image set by `set_image`.
+    ///
+    /// # Example
+    /// ```
+    /// use win_toast_notify::{WinToastNotify, ImageAlign, InlineImage};
+    ///
+    /// WinToastNotify::new()
+    ///     .set_title("Nightly benchmarks")
+    ///     .set_images(vec![
+    ///         InlineImage::new(r"C:\Windows\Web\Screen\img100.jpg").set_alt("Throughput, up 4%"),
+    ///         InlineImage::new(r"C:\Windows\Web\Screen\img101.jpg")
+    ///             .set_alt("Latency, unchanged")
+    ///             .set_remove_margin(true)
+    ///             .set_align(ImageAlign::Center),
+    ///     ])
+    ///     .show()
+    ///     .expect("Failed to show toast notification");
+    /// ```
+    pub fn set_images(mut self, images: Vec<InlineImage>) -> Self {
+        self.images = Some(images);
+        self
+    }
+
     /// Set the actions.
     /// # Example
     /// ```
@@ -172,19 +308,22 @@ impl WinToastNotify {
     ///             activation_type: ActivationType::Protocol,
     ///             action_content: "Url".to_string(),
     ///             arguments: "https://www.google.com/".to_string(),
-    ///             image_url: None
+    ///             image_url: None,
+    ///             button_style: None
     ///         },
     ///         Action {
     ///             activation_type: ActivationType::Protocol,
     ///             action_content: "File".to_string(),
     ///             arguments: r"C:\Windows\Web\Screen\img104.jpg".to_string(),
-    ///             image_url: None
+    ///             image_url: None,
+    ///             button_style: None
     ///         },
     ///         Action {
     ///             activation_type: ActivationType::Protocol,
     ///             action_content: "Folder".to_string(),
-    ///             arguments: r"$env:USERPROFILE\Downloads".to_string(),   // PowerShell supports using environment variables
-    ///             image_url: None
+    ///             arguments: r"$env:USERPROFILE\Downloads".to_string(),   // Environment variables are expanded for protocol activation
+    ///             image_url: None,
+    ///             button_style: None
     ///         }
     ///     ])
     ///     .show()
@@ -199,6 +338,81 @@ impl WinToastNotify {
         self
     }
 
+    /// Set the tag that identifies the notification within its group.
+    ///
+    /// Showing a notification with the same tag and group replaces the previous one instead of adding another.
+    /// When a progress bar is set, the tag of the progress bar is used.
+    ///
+    /// # Example
+    /// ```
+    /// use win_toast_notify::WinToastNotify;
+    ///
+    /// // Keep a single "CI status" notification per branch
+    /// for status in ["Build started", "Build passed"] {
+    ///     WinToastNotify::new()
+    ///         .set_tag("main")
+    ///         .set_group("ci-status")
+    ///         .set_title("CI status: main")
+    ///         .set_messages(vec![status])
+    ///         .show()
+    ///         .expect("Failed to show toast notification");
+    /// }
+    /// ```
+    pub fn set_tag(mut self, tag: &str) -> Self {
+        self.tag = Some(tag.to_string());
+        self
+    }
+
+    /// Set the group of the notification, see [set_tag](#method.set_tag).
+    pub fn set_group(mut self, group: &str) -> Self {
+        self.group = Some(group.to_string());
+        self
+    }
+
+    /// Set the time after which the notification is removed from Action Center.
+    ///
+    /// Useful for notifications that become stale, such as the status of a build.
+    pub fn set_expiration_time(mut self, time: SystemTime) -> Self {
+        self.expiration_time = Some(time);
+        self
+    }
+
+    /// Remove the notification from Action Center when the system reboots.
+    pub fn set_expires_on_reboot(mut self, expires_on_reboot: bool) -> Self {
+        self.expires_on_reboot = expires_on_reboot;
+        self
+    }
+
+    /// [Microsoft Docs about Priority](https://learn.microsoft.com/en-us/uwp/api/windows.ui.notifications.toastnotification.priority)
+    pub fn set_priority(mut self, priority: Priority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Group the notification under a header in Action Center, Windows 10 1703 and later.
+    pub fn set_header(mut self, header: Header) -> Self {
+        self.header = Some(header);
+        self
+    }
+
+    /// Deliver the notification silently to Action Center, without showing a popup.
+    pub fn set_suppress_popup(mut self, suppress_popup: bool) -> Self {
+        self.suppress_popup = suppress_popup;
+        self
+    }
+
+    /// Whether the notification is mirrored to the user's other devices.
+    pub fn set_mirroring(mut self, mirroring: NotificationMirroring) -> Self {
+        self.mirroring = mirroring;
+        self
+    }
+
+    /// Set the ID that correlates this notification with the same notification on other devices.
+    pub fn set_remote_id(mut self, remote_id: &str) -> Self {
+        self.remote_id = Some(remote_id.to_string());
+        self
+    }
+
     /// Set Progress.
     /// 
     /// tag: Define a tag (and optionally a group) to uniquely identify the notification, in order update the notification data later;
@@ -227,8 +441,9 @@ impl WinToastNotify {
     ///             Action {
     ///                 activation_type: ActivationType::Protocol,
     ///                 action_content: "Open Downloaads Folder".to_string(),
-    ///                 arguments: r"$env:USERPROFILE\Downloads".to_string(),   // PowerShell supports using environment variables
-    ///                 image_url: None
+    ///                 arguments: r"$env:USERPROFILE\Downloads".to_string(),   // Environment variables are expanded for protocol activation
+    ///                 image_url: None,
+    ///                 button_style: None
     ///             },
     ///         ])
     ///         .show()
@@ -261,49 +476,16 @@ impl WinToastNotify {
     }
     
     /// Update the notification progress for the specified APP ID and tag
+    ///
+    /// Use [ToastNotifier::progress_update](struct.ToastNotifier.html#method.progress_update) to run the
+    /// middleware of a notifier.
     pub fn progress_update(
         app_id: Option<&str>,
         tag: &str,
         value: f32,
         value_string: &str
     ) -> Result<(), Box<dyn std::error::Error>> {
-        let mut command = String::new();
-        write!(
-            command,
-            r#"
-            $Dictionary = [System.Collections.Generic.Dictionary[String, String]]::New()
-            $Dictionary.Add('progressValue', {})
-            $Dictionary.Add('progressValueString', "{}")
-            $NotificationData = [Windows.UI.Notifications.NotificationData]::New($Dictionary)
-            $NotificationData.SequenceNumber = 2
-            $AppId = '{}'
-            $Notifier = [Windows.UI.Notifications.ToastNotificationManager, Windows.UI.Notifications, ContentType = WindowsRuntime]::CreateToastNotifier($AppId)
-            $Notifier.Update($NotificationData, '{}')
-            "#,
-            value,
-            value_string,
-            match app_id{
-                Some(app_id) => app_id,
-                None => r"{1AC14E77-02E7-4E5D-B744-2EB1AE5198B7}\WindowsPowerShell\v1.0\powershell.exe",
-            },
-            tag,
-        )?;
-
-        // Run it by PowerShell
-        let output = Command::new("powershell")
-            .creation_flags(0x08000000)
-            .args(["-Command", &command])
-            .output()
-            .map_err(|e| format!("Failed to execute process: {}", e))?;
-        if !output.status.success() {
-            return Err(format!(
-                "Failed to execute command: {}",
-                String::from_utf8_lossy(&output.stderr)
-            )
-            .into());
-        }
-
-        Ok(())
+        ToastNotifier::new(app_id).progress_update(tag, value, value_string)
     }
 
     pub fn progress_complete(
@@ -312,49 +494,18 @@ impl WinToastNotify {
         status: &str,
         value_string: &str
     ) -> Result<(), Box<dyn std::error::Error>> {
-        let mut command = String::new();
-        write!(
-            command,
-            r#"
-            $Dictionary = [System.Collections.Generic.Dictionary[String, String]]::New()
-            $Dictionary.Add('progressStatus', '{}')
-            $Dictionary.Add('progressValue', 1)
-            $Dictionary.Add('progressValueString', "{}")
-            $NotificationData = [Windows.UI.Notifications.NotificationData]::New($Dictionary)
-            $NotificationData.SequenceNumber = 2
-            $AppId = '{}'
-            $Notifier = [Windows.UI.Notifications.ToastNotificationManager, Windows.UI.Notifications, ContentType = WindowsRuntime]::CreateToastNotifier($AppId)
-            $Notifier.Update($NotificationData, '{}')
-            "#,
-            status,
-            value_string,
-            match app_id {
-                Some(app_id) => app_id,
-                None => 
-                    r"{1AC14E77-02E7-4E5D-B744-2EB1AE5198B7}\WindowsPowerShell\v1.0\powershell.exe",
-            },
-            tag,
-        )?;
-        // Run it by PowerShell
-        let output = Command::new("powershell")
-            .creation_flags(0x08000000)
-            .args(["-Command", &command])
-            .output()
-            .map_err(|e| format!("Failed to execute process: {}", e))?;
-        if !output.status.success() {
-            return Err(format!(
-                "Failed to execute command: {}",
-                String::from_utf8_lossy(&output.stderr)
-            ).into());
-        }
-
-        Ok(())
+        ToastNotifier::new(app_id).progress_complete(tag, status, value_string)
     }
 
     /// Set the notification sound and whether the sound should loop.
     /// 
     /// Default is [Audio::WinDefault](enum.Audio.html)
     /// 
+    /// A sound file set with `Audio::From` is played by a PowerShell process that keeps running while the
+    /// notification is shown, and stops it when the notification is activated, dismissed or expires.
+    /// Paths and URIs are accepted as for [MediaSource](enum.MediaSource.html), in the formats
+    /// wav, mp3, wma, m4a, aac and flac.
+    /// 
     /// # Example
     /// ```
     /// use win_toast_notify::{WinToastNotify, Audio, Loop};
@@ -365,11 +516,11 @@ impl WinToastNotify {
     ///     .show()
     ///     .expect("Failed to show toast notification");
     /// 
-    /// // Use other audio, but don't loop it.(Currently unable to play other audio sources for unknown reasons)
-    /// // WinToastNotify::new()
-    /// //    .set_audio(Audio::From(r"C:\Windows\Media\Ring05.wav"), Loop::False)
-    /// //    .show()
-    /// //    .expect("Failed to show toast notification");
+    /// // Use other audio, but don't loop it.
+    /// WinToastNotify::new()
+    ///     .set_audio(Audio::From(r"C:\Windows\Media\Ring05.wav".to_string()), Loop::False)
+    ///     .show()
+    ///     .expect("Failed to show toast notification");
     /// ```
     pub fn set_audio(mut self, audio: Audio, audio_loop: Loop) -> Self {
         self.audio = Some(audio);
@@ -377,16 +528,51 @@ impl WinToastNotify {
         self
     }
 
+    /// Set the Windows build to render the notification for, instead of the running system.
+    ///
+    /// See [TargetPlatform](struct.TargetPlatform.html) for how unsupported features are replaced.
+    pub fn set_target_platform(mut self, platform: TargetPlatform) -> Self {
+        self.platform = Some(platform);
+        self
+    }
+
     /// Show the notification.
-    pub fn show(&self) -> Result<(), Box<dyn std::error::Error>> {
+    ///
+    /// Returns a [ToastHandle](struct.ToastHandle.html) to hide, remove or replace the notification later.
+    pub fn show(&self) -> Result<ToastHandle, Box<dyn std::error::Error>> {
+        ToastNotifier::new(self.app_id.as_deref()).show(self)
+    }
+
+    /// Render the toast XML for the target platform.
+    ///
+    /// Local images are replaced by their variant for the [DisplaySettings](struct.DisplaySettings.html),
+    /// and images set from bytes are written to the default [ImageCache](struct.ImageCache.html).
+    pub fn to_xml(&self) -> Result<String, Box<dyn std::error::Error>> {
+        self.to_xml_with(&powershell::Options::default())
+    }
+
+    /// Render the toast XML, detecting the display settings and platform with the timeout and cancellation of a
+    /// notifier.
+    pub(crate) fn to_xml_with(&self, options: &powershell::Options) -> Result<String, Box<dyn std::error::Error>> {
+        for source in self.sources() {
+            source.check()?;
+        }
+        let mut toast = self.clone();
+        toast.resolve_variants(options);
+        if toast.sources().any(|source| matches!(source, MediaSource::Bytes(_))) {
+            ImageCache::default().localize_toast(&mut toast, false, options)?;
+        }
+        let platform = toast.platform.unwrap_or_else(|| TargetPlatform::detect_with(options));
+        Ok(toast.render(platform)?)
+    }
+
+    fn render(&self, platform: TargetPlatform) -> Result<String, std::fmt::Error> {
         // Create a String instance and preallocate 2000 bytes of memory for it, reduce the number of memory reallocations
-        let mut command = String::with_capacity(2000);
-        // Start of XML
-        command.push_str("$xml = @\"");
+        let mut xml = String::with_capacity(2000);
         write!(
-            command,
+            xml,
             r#"
-            <toast{}{}{}>
+            <toast{}{}{}{}>
                 <visual>
                     <binding template="ToastGeneric">
                         {}
@@ -394,16 +580,21 @@ impl WinToastNotify {
                         {}
                         {}
                         {}
+                        {}
                     </binding>
                 </visual>
                 <actions>
                     {}
                 </actions>
                 {}
+                {}
             </toast>
             "#,
             match &self.open {
-                Some(url_or_path) => format!(r#" activationType="protocol" launch="{}""#, url_or_path),
+                Some(url_or_path) => format!(
+                    r#" activationType="protocol" launch="{}""#,
+                    escape_str_attribute(&powershell::expand_env(url_or_path))
+                ),
                 None => String::new(),
             },
             match &self.duration {
@@ -415,41 +606,63 @@ impl WinToastNotify {
                 Scenario::Reminder => " scenario=\"reminder\"",
                 Scenario::Alarm => " scenario=\"alarm\"",
                 Scenario::IncomingCall => " scenario=\"incomingCall\"",
-                Scenario::Urgent => " scenario=\"urgent\"",
+                Scenario::Urgent if platform.supports(Capability::UrgentScenario) => " scenario=\"urgent\"",
+                Scenario::Urgent => " scenario=\"reminder\"",
             },
+            if self.uses_button_styles(platform) { " useButtonStyle=\"true\"" } else { "" },
             match (&self.logo, &self.logo_circle) {
                 (Some(logo), CropCircle::True) => format!(
-                    "\n<image placement=\"appLogoOverride\" hint-crop=\"circle\" src=\"{}\"/>",
-                    &logo
+                    "\n<image placement=\"appLogoOverride\" hint-crop=\"circle\" src=\"{}\"{}/>",
+                    escape_str_attribute(&logo.uri()),
+                    alt_attribute(&self.logo_alt)
+                ),
+                (Some(logo), CropCircle::False) => format!(
+                    "\n<image placement=\"appLogoOverride\" src=\"{}\"{}/>",
+                    escape_str_attribute(&logo.uri()),
+                    alt_attribute(&self.logo_alt)
                 ),
-                (Some(logo), CropCircle::False) =>
-                    format!("\n<image placement=\"appLogoOverride\" src=\"{}\"/>", &logo),
                 (None, _) => String::new(),
             },
             match &self.title {
-                Some(title) => format!("\n<text>{}</text>", &title),
+                Some(title) => format!("\n<text>{}</text>", escape_str_attribute(title)),
                 None => String::new(),
             },
             match &self.messages {
                 Some(messages) => messages.iter().fold(String::new(), |acc, message| {
-                    format!("{}\n<text>{}</text>", acc, message)
+                    format!("{}\n<text>{}</text>", acc, escape_str_attribute(message))
                 }),
                 None => String::new(),
             },
             match (&self.image, &self.image_placement) {
-                (Some(image), ImagePlacement::Top) =>
-                    format!("\n<image placement=\"hero\" src=\"{}\"/>", &image),
-                (Some(image), ImagePlacement::Bottom) => format!("\n<image src=\"{}\"/>", &image),
+                (Some(image), ImagePlacement::Top) if platform.supports(Capability::HeroImage) => format!(
+                    "\n<image placement=\"hero\" src=\"{}\"{}/>",
+                    escape_str_attribute(&image.uri()),
+                    alt_attribute(&self.image_alt)
+                ),
+                (Some(image), _) => format!(
+                    "\n<image src=\"{}\"{}/>",
+                    escape_str_attribute(&image.uri()),
+                    alt_attribute(&self.image_alt)
+                ),
                 (None, _) => String::new(),
             },
+            self.images.iter().flatten().fold(String::new(), |acc, image| {
+                format!("{}\n{}", acc, image.to_xml())
+            }),
             match &self.progress {
-                Some(_) => format!(
+                Some(progress) if !platform.supports(Capability::ProgressBar) => format!(
+                    "\n<text>{}: {} {}</text>",
+                    escape_str_attribute(&progress.title),
+                    escape_str_attribute(&progress.status),
+                    escape_str_attribute(&progress.value_string)
+                ),
+                Some(_) => String::from(
                     r#"
                     <progress
-                        title="{{progressTitle}}"
-                        value="{{progressValue}}"
-                        valueStringOverride="{{progressValueString}}"
-                        status="{{progressStatus}}"/>
+                        title="{progressTitle}"
+                        value="{progressValue}"
+                        valueStringOverride="{progressValueString}"
+                        status="{progressStatus}"/>
                     "#
                 ),
                 None => String::new(),
@@ -457,106 +670,154 @@ impl WinToastNotify {
             match &self.actions {
                 Some(actions) => actions.iter().fold(String::new(), |acc, action| {
                     format!(
-                        "{}\n<action content=\"{}\" activationType=\"{}\" arguments=\"{}\" {} />",
+                        "{}\n<action content=\"{}\" activationType=\"{}\" arguments=\"{}\" {}{} />",
                         acc,
                         escape_str_attribute(&action.action_content).into_owned(),
                         action.activation_type.as_str(),
-                        escape_str_attribute(&action.arguments).into_owned(),
-                        action.image_url.clone().map_or_else(
-                            || String::new(),
-                            |url| format!("imageUri=\"{}\"", escape_str_attribute(url.trim()).into_owned())),
+                        escape_str_attribute(&action.arguments_uri()).into_owned(),
+                        action.image_url.as_ref().map_or_else(
+                            String::new,
+                            |url| format!("imageUri=\"{}\"", escape_str_attribute(&url.uri()))),
+                        match &action.button_style {
+                            Some(style) if platform.supports(Capability::ButtonStyle) =>
+                                format!(" hint-buttonStyle=\"{}\"", style.as_str()),
+                            _ => String::new(),
+                        },
                     )
                 }),
                 None => String::new(),
             },
             match &self.audio {
                 Some(audio) => match (audio, &self.audio_loop) {
-                    (Audio::From(_), _) => String::from("\n<audio silent=\"true\" />"),
+                    // Played by the helper process, see `helper_script`
+                    (Audio::From(source), _) if !audio::is_sound_event(source) =>
+                        String::from("\n<audio silent=\"true\" />"),
+                    _ if self.speech_replaces_sound() => String::from("\n<audio silent=\"true\" />"),
                     (Audio::Silent, _) => String::from("\n<audio silent=\"true\" />"),
                     (_, Loop::False) => format!("\n<audio src=\"{}\" />", audio.as_str()),
                     (_, Loop::True) =>
                         format!("\n<audio src=\"{}\" loop=\"true\" />", audio.as_str()),
                 },
                 None => String::from("\n<audio silent=\"true\" />"),
-            }
-        )?;
-        // End of XML (The terminator ("@") cannot be preceded by a space)
-        command.push_str("\n\"@");
-        // Powershell commands that send Toast notifications
-        write!(
-            command,
-            r#"
-            $XmlDocument = [Windows.Data.Xml.Dom.XmlDocument, Windows.Data.Xml.Dom.XmlDocument, ContentType = WindowsRuntime]::New()
-            $XmlDocument.loadXml($xml)
-            $AppId = '{}'
-            {}
-            "#,
-            match &self.app_id {
-                Some(id) => id,
-                None =>
-                    r"{1AC14E77-02E7-4E5D-B744-2EB1AE5198B7}\WindowsPowerShell\v1.0\powershell.exe",
-            },
-            match &self.progress {
-                Some(progress) => {format!(
-                        "
-                        $ToastNotification = [Windows.UI.Notifications.ToastNotification, Windows.UI.Notifications, ContentType = WindowsRuntime]::New($XmlDocument)
-                        $ToastNotification.Tag = '{}'
-                        $Dictionary = [System.Collections.Generic.Dictionary[String, String]]::New()
-                        $Dictionary.Add('progressTitle', '{}')
-                        $Dictionary.Add('progressValue', '{}')
-                        $Dictionary.Add('progressValueString', '{}')
-                        $Dictionary.Add('progressStatus', '{}')
-                        $ToastNotification.Data = [Windows.UI.Notifications.NotificationData]::New($Dictionary)
-                        $ToastNotification.Data.SequenceNumber = 1
-                        [Windows.UI.Notifications.ToastNotificationManager, Windows.UI.Notifications, ContentType = WindowsRuntime]::CreateToastNotifier($AppId).Show($ToastNotification)
-                        ",
-                        progress.tag,
-                        progress.title,
-                        &progress.value,
-                        &progress.value_string,
-                        &progress.status
-                    )
-                },
-                None => "[Windows.UI.Notifications.ToastNotificationManager, Windows.UI.Notifications, ContentType = WindowsRuntime]::CreateToastNotifier($AppId).Show($XmlDocument)".into(),
             },
+            match &self.header {
+                Some(header) if platform.supports(Capability::Header) => format!(
+                    "\n<header id=\"{}\" title=\"{}\" arguments=\"{}\" />",
+                    escape_str_attribute(&header.id),
+                    escape_str_attribute(&header.title),
+                    escape_str_attribute(&header.arguments)
+                ),
+                _ => String::new(),
+            }
         )?;
-        // Add Audio Source
-        if let Some(Audio::From(url)) = &self.audio {
+        Ok(xml)
+    }
+
+    /// Whether a button is colored, which Windows only does if the toast opts in.
+    fn uses_button_styles(&self, platform: TargetPlatform) -> bool {
+        platform.supports(Capability::ButtonStyle)
+            && self.actions.iter().flatten().any(|action| action.button_style.is_some())
+    }
+
+    /// PowerShell that creates `$ToastNotification` from `$XmlDocument`.
+    pub(crate) fn notification_script(&self, tag: &str, group: Option<&str>) -> Result<String, std::fmt::Error> {
+        let mut script = String::from(
+            "$ToastNotification = [Windows.UI.Notifications.ToastNotification, Windows.UI.Notifications, ContentType = WindowsRuntime]::New($XmlDocument)\n",
+        );
+        script.push_str(&self.properties_script("$ToastNotification")?);
+        if self.expires_on_reboot {
+            script.push_str("$ToastNotification.ExpiresOnReboot = $true\n");
+        }
+        if let Priority::High = self.priority {
+            script.push_str("$ToastNotification.Priority = [Windows.UI.Notifications.ToastNotificationPriority]::High\n");
+        }
+        writeln!(script, "$ToastNotification.Tag = {}", powershell::quote(tag))?;
+        if let Some(group) = group {
+            writeln!(script, "$ToastNotification.Group = {}", powershell::quote(group))?;
+        }
+        if let Some(progress) = &self.progress {
             write!(
-                command,
-                r#"
-                $MediaPlayer = [Windows.Media.Playback.MediaPlayer, Windows.Media, ContentType = WindowsRuntime]::New()
-                $MediaPlayer.Source = [Windows.Media.Core.MediaSource]::CreateFromUri('{}')
-                $MediaPlayer.Play()
-                "#,
-                escape_str_attribute(url.trim()).into_owned()
-            )?
+                script,
+                "
+                $Dictionary = [System.Collections.Generic.Dictionary[String, String]]::New()
+                $Dictionary.Add('progressTitle', {})
+                $Dictionary.Add('progressValue', '{}')
+                $Dictionary.Add('progressValueString', {})
+                $Dictionary.Add('progressStatus', {})
+                $ToastNotification.Data = [Windows.UI.Notifications.NotificationData]::New($Dictionary)
+                $ToastNotification.Data.SequenceNumber = 1
+                ",
+                powershell::quote(&progress.title),
+                &progress.value,
+                powershell::quote(&progress.value_string),
+                powershell::quote(&progress.status)
+            )?;
         }
-        // Run it by PowerShell
-        let output = Command::new("powershell")
-            .creation_flags(0x08000000)
-            .args(["-Command", &command])
-            .output()
-            .map_err(|e| format!("Failed to execute process: {}", e))?;
-        if !output.status.success() {
-            return Err(format!(
-                "Failed to execute command: {}",
-                String::from_utf8_lossy(&output.stderr)
-            )
-            .into());
+        Ok(script)
+    }
+
+    /// The images of the notification.
+    pub(crate) fn sources(&self) -> impl Iterator<Item = &MediaSource> {
+        self.logo
+            .iter()
+            .chain(self.image.iter())
+            .chain(self.images.iter().flatten().map(|image| &image.source))
+            .chain(self.actions.iter().flatten().filter_map(|action| action.image_url.as_ref()))
+    }
+
+    pub(crate) fn sources_mut(&mut self) -> impl Iterator<Item = &mut MediaSource> {
+        self.logo
+            .iter_mut()
+            .chain(self.image.iter_mut())
+            .chain(self.images.iter_mut().flatten().map(|image| &mut image.source))
+            .chain(self.actions.iter_mut().flatten().filter_map(|action| action.image_url.as_mut()))
+    }
+
+    /// The tag of the notification: the progress tag if a progress bar is set, otherwise the tag set by `set_tag`.
+    pub(crate) fn effective_tag(&self) -> Option<&str> {
+        match &self.progress {
+            Some(progress) => Some(&progress.tag),
+            None => self.tag.as_deref(),
         }
+    }
 
-        Ok(())
+    /// PowerShell that sets the properties shared by `ToastNotification` and `ScheduledToastNotification`.
+    pub(crate) fn properties_script(&self, variable: &str) -> Result<String, std::fmt::Error> {
+        let mut script = String::new();
+        if let Some(time) = self.expiration_time {
+            writeln!(
+                script,
+                "{}.ExpirationTime = [DateTimeOffset]::FromUnixTimeMilliseconds({})",
+                variable,
+                notifier::unix_millis(time)
+            )?;
+        }
+        if self.suppress_popup {
+            writeln!(script, "{}.SuppressPopup = $true", variable)?;
+        }
+        if let NotificationMirroring::Disabled = self.mirroring {
+            writeln!(
+                script,
+                "{}.NotificationMirroring = [Windows.UI.Notifications.NotificationMirroring]::Disabled",
+                variable
+            )?;
+        }
+        if let Some(remote_id) = &self.remote_id {
+            writeln!(script, "{}.RemoteId = {}", variable, powershell::quote(remote_id))?;
+        }
+        Ok(script)
     }
 }
 
 // Duration of notification
+#[derive(Debug, Clone)]
 pub enum Duration {
     Short,
     Long,
 }
 
 // The scenario your toast is used for, like an alarm or reminder.
+#[derive(Debug, Clone)]
 pub enum Scenario {
     None,
     Reminder,
@@ -565,14 +826,73 @@ pub enum Scenario {
     Urgent,
 }
 
+/// A button of the notification.
+///
+/// Use [ToastArguments](struct.ToastArguments.html) to build structured `arguments`,
+/// and `MediaSource::from(bytes)` for an icon generated at runtime. The `arguments` of protocol activation expand
+/// environment variables such as `$env:USERPROFILE`, other arguments are passed as they are.
+#[derive(Debug, Clone)]
 pub struct Action {
     pub activation_type: ActivationType,
     pub action_content: String,
     pub arguments: String,
-    pub image_url: Option<String>,
+    pub image_url: Option<MediaSource>,
+    /// The color of the button, Windows 11 shows a plain button on earlier builds.
+    pub button_style: Option<ButtonStyle>,
+}
+
+impl Action {
+    /// The arguments as passed to Windows, with environment variables expanded for protocol activation.
+    fn arguments_uri(&self) -> Cow<'_, str> {
+        match self.activation_type {
+            ActivationType::Protocol => powershell::expand_env(&self.arguments),
+            _ => Cow::Borrowed(&self.arguments),
+        }
+    }
+}
+
+/// The color of a button.
+///
+/// # Examples
+/// ```
+/// use win_toast_notify::{Action, ActivationType, ButtonStyle, TargetPlatform, WinToastNotify};
+///
+/// let toast = WinToastNotify::new().set_actions(vec![Action {
+///     activation_type: ActivationType::Foreground,
+///     action_content: "Decline".to_string(),
+///     arguments: "decline".to_string(),
+///     image_url: None,
+///     button_style: Some(ButtonStyle::Critical),
+/// }]);
+///
+/// let xml = toast.clone().set_target_platform(TargetPlatform::build(22631)).to_xml().unwrap();
+/// assert!(xml.contains(r#"useButtonStyle="true""#) && xml.contains(r#"hint-buttonStyle="Critical""#));
+///
+/// // Windows 10 shows a plain button
+/// let xml = toast.set_target_platform(TargetPlatform::build(19045)).to_xml().unwrap();
+/// assert!(!xml.contains("useButtonStyle") && !xml.contains("hint-buttonStyle"));
+/// ```
+///
+/// [Microsoft Docs about button styles](https://learn.microsoft.com/en-us/windows/apps/design/shell/tiles-and-notifications/adaptive-interactive-toasts#button-style)
+#[derive(Debug, Clone)]
+pub enum ButtonStyle {
+    /// A green button, for actions such as accepting a call.
+    Success,
+    /// A red button, for actions such as declining a call.
+    Critical,
+}
+
+impl ButtonStyle {
+    pub fn as_str(&self) -> &str {
+        match self {
+            ButtonStyle::Success => "Success",
+            ButtonStyle::Critical => "Critical",
+        }
+    }
 }
 
 /// [Microsoft Docs about Button](https://learn.microsoft.com/en-us/uwp/schemas/tiles/toastschema/element-action)
+#[derive(Debug, Clone)]
 pub enum ActivationType {
     Protocol, // 使用协议激活功能启动不同的应用程序
     System,
@@ -592,16 +912,104 @@ impl ActivationType {
 }
 
 // Crop the LOGO into a circle
+#[derive(Debug, Clone)]
 pub enum CropCircle {
     True,
     False,
 }
 
+#[derive(Debug, Clone)]
 pub enum ImagePlacement {
     Top,
     Bottom,
 }
 
+/// An image shown inline in the notification, see [set_images](struct.WinToastNotify.html#method.set_images).
+///
+/// [Microsoft Docs about Image](https://learn.microsoft.com/en-us/uwp/schemas/tiles/toastschema/element-image)
+#[derive(Debug, Clone)]
+pub struct InlineImage {
+    pub source: MediaSource,
+    /// The text read by screen readers.
+    pub alt: Option<String>,
+    pub hint_crop: CropCircle,
+    /// Remove the margin around the image.
+    pub remove_margin: bool,
+    pub align: Option<ImageAlign>,
+}
+
+impl InlineImage {
+    /// An image from a path, URI or data, see [MediaSource](enum.MediaSource.html).
+    pub fn new(source: impl Into<MediaSource>) -> Self {
+        Self {
+            source: source.into(),
+            alt: None,
+            hint_crop: CropCircle::False,
+            remove_margin: false,
+            align: None,
+        }
+    }
+
+    pub fn set_alt(mut self, alt: &str) -> Self {
+        self.alt = Some(alt.to_string());
+        self
+    }
+
+    pub fn set_crop(mut self, hint_crop: CropCircle) -> Self {
+        self.hint_crop = hint_crop;
+        self
+    }
+
+    pub fn set_remove_margin(mut self, remove_margin: bool) -> Self {
+        self.remove_margin = remove_margin;
+        self
+    }
+
+    pub fn set_align(mut self, align: ImageAlign) -> Self {
+        self.align = Some(align);
+        self
+    }
+
+    fn to_xml(&self) -> String {
+        format!(
+            "<image src=\"{}\"{}{}{}{}/>",
+            escape_str_attribute(&self.source.uri()),
+            alt_attribute(&self.alt),
+            match self.hint_crop {
+                CropCircle::True => " hint-crop=\"circle\"",
+                CropCircle::False => "",
+            },
+            if self.remove_margin { " hint-removeMargin=\"true\"" } else { "" },
+            self.align.as_ref().map_or_else(String::new, |align| format!(" hint-align=\"{}\"", align.as_str())),
+        )
+    }
+}
+
+/// Horizontal alignment of an [InlineImage](struct.InlineImage.html).
+#[derive(Debug, Clone)]
+pub enum ImageAlign {
+    Stretch,
+    Left,
+    Center,
+    Right,
+}
+
+impl ImageAlign {
+    pub fn as_str(&self) -> &str {
+        match self {
+            ImageAlign::Stretch => "stretch",
+            ImageAlign::Left => "left",
+            ImageAlign::Center => "center",
+            ImageAlign::Right => "right",
+        }
+    }
+}
+
+fn alt_attribute(alt: &Option<String>) -> String {
+    alt.as_ref().map_or_else(String::new, |alt| format!(" alt=\"{}\"", escape_str_attribute(alt)))
+}
+
+#[derive(Debug, Clone)]
 pub struct Progress {
     pub tag: String,
     pub title: String,
@@ -610,7 +1018,35 @@ pub struct Progress {
     pub value_string: String,
 }
 
+/// A header that groups notifications in Action Center.
+///
+/// [Microsoft Docs about Header](https://learn.microsoft.com/en-us/uwp/schemas/tiles/toastschema/element-header)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Header {
+    /// Notifications with the same ID are grouped under the same header.
+    pub id: String,
+    pub title: String,
+    /// Passed to the app when the header is clicked.
+    pub arguments: String,
+}
+
+impl Header {
+    pub fn new(id: &str, title: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            title: title.to_string(),
+            arguments: String::new(),
+        }
+    }
+
+    pub fn set_arguments(mut self, arguments: &str) -> Self {
+        self.arguments = arguments.to_string();
+        self
+    }
+}
+
 // System Audio
+#[derive(Debug, Clone)]
 pub enum Audio {
     From(String),
     Silent,
@@ -675,7 +1111,22 @@ impl Audio {
     }
 }
 
+/// [Microsoft Docs about Priority](https://learn.microsoft.com/en-us/uwp/api/windows.ui.notifications.toastnotificationpriority)
+#[derive(Debug, Clone)]
+pub enum Priority {
+    Default,
+    High,
+}
+
+/// Whether a notification is mirrored to other devices.
+#[derive(Debug, Clone)]
+pub enum NotificationMirroring {
+    Allowed,
+    Disabled,
+}
+
 // Audio Loop
+#[derive(Debug, Clone)]
 pub enum Loop {
     True,
     False,
@@ -0,0 +1,252 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::{powershell, ImageCache};
+
+/// The source of an image or sound.
+///
+/// Converting from a string recognizes `http(s)://`, `ms-appdata:///`, `ms-appx:///` and `file:///` URIs,
+/// anything else is a file path. Relative paths are resolved against the current directory, and environment
+/// variables such as `$env:USERPROFILE` are expanded.
+///
+/// # Examples
+/// ```
+/// use win_toast_notify::{CropCircle, MediaSource, WinToastNotify};
+///
+/// let file = MediaSource::from(r"C:\Users\Public\Pictures\My Logo.png");
+/// assert_eq!(file.to_uri().unwrap(), "file:///C:/Users/Public/Pictures/My%20Logo.png");
+///
+/// let share = MediaSource::from(r"\\server\share\logo.png");
+/// assert_eq!(share.to_uri().unwrap(), "file://server/share/logo.png");
+///
+/// let remote = MediaSource::from("https://example.com/cover.jpg");
+/// assert_eq!(remote, MediaSource::Http("https://example.com/cover.jpg".to_string()));
+///
+/// // URLs are checked when the notification is rendered
+/// let toast = WinToastNotify::new().set_logo(MediaSource::Http("https://exa mple.com".to_string()), CropCircle::False);
+/// assert!(toast.to_xml().is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum MediaSource {
+    File(PathBuf),
+    /// An `http://` or `https://` URL, checked when the notification is rendered.
+    Http(String),
+    /// A path in the app data of a packaged app, such as `local/logo.png`.
+    AppData(String),
+    /// A path in the package of a packaged app, such as `Assets/logo.png`.
+    AppX(String),
+    /// Image data, written to a file named by its hash in the [ImageCache](struct.ImageCache.html) before rendering.
+    Bytes(Arc<[u8]>),
+}
+
+impl MediaSource {
+    /// The URI of the source as expected by Windows.
+    ///
+    /// Image data is first written to the default [ImageCache](struct.ImageCache.html), which fails if it is not
+    /// a supported image or cannot be written.
+    pub fn to_uri(&self) -> Result<String, Box<dyn std::error::Error>> {
+        match self {
+            MediaSource::Bytes(bytes) => Ok(file_uri(&ImageCache::default().store(bytes)?)),
+            _ => Ok(self.uri()),
+        }
+    }
+
+    /// The URI of the source, for image data the file it is stored at by the default cache,
+    /// which must already be written.
+    pub(crate) fn uri(&self) -> String {
+        match self {
+            MediaSource::File(path) => file_uri(path),
+            MediaSource::Http(url) => url.clone(),
+            MediaSource::AppData(path) => format!("ms-appdata:///{}", path.trim_start_matches('/')),
+            MediaSource::AppX(path) => format!("ms-appx:///{}", path.trim_start_matches('/')),
+            MediaSource::Bytes(bytes) => file_uri(&ImageCache::default().path_for(bytes)),
+        }
+    }
+
+    /// Fail if the source is a URL that is not a valid `http://` or `https://` URL.
+    pub(crate) fn check(&self) -> Result<(), Box<dyn std::error::Error>> {
+        match self {
+            MediaSource::Http(url) => check_url(url).map_err(|e| format!("Invalid URL \"{}\": {}", url, e).into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// The local path of a file source.
+    pub fn as_path(&self) -> Option<&Path> {
+        match self {
+            MediaSource::File(path) => Some(path),
+            _ => None,
+        }
+    }
+}
+
+impl From<&str> for MediaSource {
+    fn from(s: &str) -> Self {
+        let s = powershell::expand_env(s.trim());
+        let s = s.as_ref();
+        let lowercase = s.to_ascii_lowercase();
+        if lowercase.starts_with("http://") || lowercase.starts_with("https://") {
+            MediaSource::Http(s.to_string())
+        } else if lowercase.starts_with("ms-appdata:///") {
+            MediaSource::AppData(s["ms-appdata:///".len()..].to_string())
+        } else if lowercase.starts_with("ms-appx:///") {
+            MediaSource::AppX(s["ms-appx:///".len()..].to_string())
+        } else if lowercase.starts_with("file:///") {
+            MediaSource::from(PathBuf::from(percent_decode(&s["file:///".len()..])))
+        } else if lowercase.starts_with("file://") {
+            // A UNC path, file://server/share
+            let path = percent_decode(&s["file:".len()..]).replace('/', r"\");
+            MediaSource::File(PathBuf::from(path))
+        } else {
+            MediaSource::from(PathBuf::from(s))
+        }
+    }
+}
+
+impl From<String> for MediaSource {
+    fn from(s: String) -> Self {
+        MediaSource::from(s.as_str())
+    }
+}
+
+impl From<&String> for MediaSource {
+    fn from(s: &String) -> Self {
+        MediaSource::from(s.as_str())
+    }
+}
+
+impl From<&[u8]> for MediaSource {
+    fn from(bytes: &[u8]) -> Self {
+        MediaSource::Bytes(bytes.into())
+    }
+}
+
+impl From<Vec<u8>> for MediaSource {
+    fn from(bytes: Vec<u8>) -> Self {
+        MediaSource::Bytes(bytes.into())
+    }
+}
+
+impl From<PathBuf> for MediaSource {
+    fn from(path: PathBuf) -> Self {
+        MediaSource::File(std::path::absolute(&path).unwrap_or(path))
+    }
+}
+
+impl From<&Path> for MediaSource {
+    fn from(path: &Path) -> Self {
+        MediaSource::from(path.to_path_buf())
+    }
+}
+
+impl From<&PathBuf> for MediaSource {
+    fn from(path: &PathBuf) -> Self {
+        MediaSource::from(path.to_path_buf())
+    }
+}
+
+fn file_uri(path: &Path) -> String {
+    let path = path.to_string_lossy();
+    // Verbatim paths: \\?\C:\dir and \\?\UNC\server\share
+    let path = match path.strip_prefix(r"\\?\") {
+        Some(rest) => match rest.strip_prefix(r"UNC\") {
+            Some(unc) => format!(r"\\{}", unc),
+            None => rest.to_string(),
+        },
+        None => path.into_owned(),
+    };
+    let path = path.replace('\\', "/");
+    match path.strip_prefix("//") {
+        Some(unc) => format!("file://{}", percent_encode(unc)),
+        None => format!("file:///{}", percent_encode(path.trim_start_matches('/'))),
+    }
+}
+
+/// Percent-encode everything but unreserved characters and path delimiters.
+fn percent_encode(s: &str) -> String {
+    let mut encoded = String::with_capacity(s.len());
+    for byte in s.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' | b':' | b'$' | b'`' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// Check that `url` is an absolute `http://` or `https://` URL with a host, without spaces or control characters.
+fn check_url(url: &str) -> Result<(), &'static str> {
+    let lowercase = url.to_ascii_lowercase();
+    let rest = ["http://", "https://"]
+        .into_iter()
+        .find_map(|scheme| lowercase.strip_prefix(scheme))
+        .ok_or("Not an http:// or https:// URL")?;
+    if url.chars().any(|c| c.is_whitespace() || c.is_control() || "\"<>\\^{|}".contains(c)) {
+        return Err("The URL contains characters that must be percent-encoded");
+    }
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    let host = authority.rsplit('@').next().unwrap_or_default();
+    let host = match host.strip_prefix('[') {
+        // An IPv6 address, [::1]:8080
+        Some(ipv6) => ipv6.split(']').next().unwrap_or_default(),
+        None => host.split(':').next().unwrap_or_default(),
+    };
+    if host.is_empty() {
+        return Err("The URL has no host");
+    }
+    Ok(())
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes.get(i + 1..i + 3).and_then(|hex| std::str::from_utf8(hex).ok());
+        match (bytes[i], hex.and_then(|hex| u8::from_str_radix(hex, 16).ok())) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expands_env_in_paths() {
+        std::env::set_var("WIN_TOAST_NOTIFY_MEDIA", r"C:\Users\Test");
+        let source = MediaSource::from(r"$env:WIN_TOAST_NOTIFY_MEDIA\Pictures\logo.png");
+        let MediaSource::File(path) = &source else {
+            panic!("Expected a file");
+        };
+        assert!(path.to_string_lossy().ends_with(r"C:\Users\Test\Pictures\logo.png"));
+        assert!(!source.uri().contains("env"));
+    }
+
+    #[test]
+    fn keeps_dollar_signs() {
+        assert_eq!(percent_encode("C:/Price $5/`a` #1.png"), "C:/Price%20$5/`a`%20%231.png");
+    }
+
+    #[test]
+    fn checks_urls() {
+        for url in ["https://example.com", "HTTP://example.com:8080/a b", "http://[::1]:8080/logo.png"] {
+            let valid = !url.contains(' ');
+            assert_eq!(MediaSource::Http(url.to_string()).check().is_ok(), valid, "{}", url);
+        }
+        for url in ["example.com/logo.png", "ftp://example.com", "https://", "https:///logo.png", "https://user@/a"] {
+            assert!(MediaSource::Http(url.to_string()).check().is_err(), "{}", url);
+        }
+    }
+}
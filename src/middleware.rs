@@ -0,0 +1,65 @@
+use std::fmt;
+use std::time::SystemTime;
+
+use crate::WinToastNotify;
+
+/// A hook that inspects, modifies or vetoes notifications before they are rendered, and observes the result,
+/// see [ToastNotifier::add_middleware](struct.ToastNotifier.html#method.add_middleware).
+///
+/// Both methods do nothing by default.
+///
+/// # Examples
+/// ```
+/// use win_toast_notify::{Middleware, Operation, WinToastNotify};
+///
+/// struct Redact;
+///
+/// impl Middleware for Redact {
+///     fn before(&self, toast: &mut WinToastNotify, _operation: Operation) -> Result<(), String> {
+///         for message in toast.messages.iter_mut().flatten() {
+///             if let Some(start) = message.find("token=") {
+///                 message.replace_range(start + 6.., "***");
+///             }
+///         }
+///         Ok(())
+///     }
+/// }
+///
+/// let mut toast = WinToastNotify::new().set_messages(vec!["Fetching https://example.com/?token=s3cr3t"]);
+/// Redact.before(&mut toast, Operation::Show).unwrap();
+/// assert_eq!(toast.messages, Some(vec!["Fetching https://example.com/?token=***".to_string()]));
+/// ```
+pub trait Middleware: Send + Sync {
+    /// Called before the notification is rendered. Return an error to veto it, with the reason.
+    fn before(&self, _toast: &mut WinToastNotify, _operation: Operation) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Called once the notification was shown, scheduled or updated, or with the error if it failed or was vetoed.
+    fn after(&self, _toast: &WinToastNotify, _operation: Operation, _result: Result<(), &dyn std::error::Error>) {}
+}
+
+/// What a [ToastNotifier](struct.ToastNotifier.html) does with a notification, as seen by its
+/// [Middleware](trait.Middleware.html).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operation {
+    /// Show now, or replace a notification shown before.
+    Show,
+    /// Schedule for delivery by Windows.
+    Schedule(SystemTime),
+    /// Update the progress of a notification. The notification has the tag and the
+    /// [Progress](struct.Progress.html) with the new status, value and value string; other changes are ignored.
+    ProgressUpdate,
+}
+
+/// The error of a notification vetoed by middleware.
+#[derive(Debug)]
+pub(crate) struct Veto(pub(crate) String);
+
+impl fmt::Display for Veto {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "The notification was vetoed: {}", self.0)
+    }
+}
+
+impl std::error::Error for Veto {}
@@ -0,0 +1,85 @@
+use std::io::Cursor;
+
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView, Rgba, RgbaImage};
+
+use crate::image_info::{self, ImageFormat};
+use crate::{MAX_IMAGE_BYTES, MAX_IMAGE_DIMENSION};
+
+/// The shape a logo is cropped to by an [ImageCache](struct.ImageCache.html) before it is shown.
+///
+/// `CropCircle::True` asks Windows to crop the logo, pre-cropping gives the same result where Windows ignores the hint.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LogoCrop {
+    /// Keep the logo as it is.
+    None,
+    /// The largest centered square.
+    Square,
+    /// The largest centered circle, with transparent corners.
+    Circle,
+}
+
+/// Convert an image to a format Windows renders and scale it down to its limits, cropping it to `crop`.
+///
+/// Returns `None` if the image can be shown as it is.
+pub(crate) fn normalize(bytes: &[u8], crop: LogoCrop) -> Result<Option<Vec<u8>>, Box<dyn std::error::Error>> {
+    let format = ImageFormat::sniff(bytes).ok_or("The data is not a supported image")?;
+    let supported = matches!(format, ImageFormat::Png | ImageFormat::Jpeg | ImageFormat::Gif);
+    let within_limits = bytes.len() as u64 <= MAX_IMAGE_BYTES
+        && image_info::dimensions(bytes)
+            .is_some_and(|(width, height)| width <= MAX_IMAGE_DIMENSION && height <= MAX_IMAGE_DIMENSION);
+    if supported && within_limits && crop == LogoCrop::None {
+        return Ok(None);
+    }
+
+    let mut image = image::load_from_memory(bytes)?;
+    image = match crop {
+        LogoCrop::None => image,
+        LogoCrop::Square => square(&image),
+        LogoCrop::Circle => DynamicImage::ImageRgba8(circle(&square(&image))),
+    };
+    if image.width() > MAX_IMAGE_DIMENSION || image.height() > MAX_IMAGE_DIMENSION {
+        image = image.resize(MAX_IMAGE_DIMENSION, MAX_IMAGE_DIMENSION, FilterType::Lanczos3);
+    }
+
+    // Photos stay JPEG, everything else becomes PNG, which keeps transparency
+    let jpeg = format == ImageFormat::Jpeg && crop != LogoCrop::Circle;
+    loop {
+        let encoded = encode(&image, jpeg)?;
+        if encoded.len() as u64 <= MAX_IMAGE_BYTES || image.width().max(image.height()) <= 64 {
+            return Ok(Some(encoded));
+        }
+        image = image.resize(image.width() * 3 / 4, image.height() * 3 / 4, FilterType::Triangle);
+    }
+}
+
+fn encode(image: &DynamicImage, jpeg: bool) -> Result<Vec<u8>, image::ImageError> {
+    let mut encoded = Vec::new();
+    if jpeg {
+        JpegEncoder::new_with_quality(&mut encoded, 85).encode_image(&DynamicImage::ImageRgb8(image.to_rgb8()))?;
+    } else {
+        image.write_to(&mut Cursor::new(&mut encoded), image::ImageFormat::Png)?;
+    }
+    Ok(encoded)
+}
+
+fn square(image: &DynamicImage) -> DynamicImage {
+    let (width, height) = image.dimensions();
+    let side = width.min(height);
+    image.crop_imm((width - side) / 2, (height - side) / 2, side, side)
+}
+
+/// Make everything outside the inscribed circle of a square image transparent, with an antialiased edge.
+fn circle(image: &DynamicImage) -> RgbaImage {
+    let mut image = image.to_rgba8();
+    let radius = image.width() as f32 / 2.0;
+    for (x, y, pixel) in image.enumerate_pixels_mut() {
+        let dx = x as f32 + 0.5 - radius;
+        let dy = y as f32 + 0.5 - radius;
+        let coverage = (radius - (dx * dx + dy * dy).sqrt() + 0.5).clamp(0.0, 1.0);
+        let Rgba([r, g, b, a]) = *pixel;
+        *pixel = Rgba([r, g, b, (a as f32 * coverage).round() as u8]);
+    }
+    image
+}
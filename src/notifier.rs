@@ -0,0 +1,846 @@
+use std::borrow::Cow;
+use std::fmt::Write;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use xml::reader::{EventReader, XmlEvent};
+
+use crate::middleware::Veto;
+use crate::{
+    audio, powershell, CancelHandle, Channels, Digest, ImageCache, Middleware, Operation, Progress, QuietHours, RateDecision,
+    RateLimit, SuppressReason, ToastHandle, WinToastNotify,
+};
+
+/// Delivers notifications on behalf of an App ID.
+///
+/// [WinToastNotify::show](struct.WinToastNotify.html#method.show) uses a notifier for the toast's own App ID;
+/// create one directly to schedule notifications or manage those already delivered.
+///
+/// # Examples
+/// ```
+/// use std::time::{Duration, SystemTime};
+/// use win_toast_notify::{WinToastNotify, ToastNotifier, Scenario, ScheduleOptions};
+///
+/// let notifier = ToastNotifier::new(None);
+/// let reminder = WinToastNotify::new()
+///     .set_scenario(Scenario::Reminder)
+///     .set_title("Stand-up meeting")
+///     .set_messages(vec!["Starts in 5 minutes"]);
+///
+/// // Delivered by Windows in 10 minutes, even if this process has exited.
+/// let id = notifier
+///     .schedule_with(
+///         &reminder,
+///         ScheduleOptions::new(SystemTime::now() + Duration::from_secs(600))
+///             .set_snooze(Duration::from_secs(300), 3),
+///     )
+///     .expect("Failed to schedule toast notification");
+///
+/// for scheduled in notifier.list_scheduled().expect("Failed to list scheduled toasts") {
+///     println!("{} {:?}", scheduled.id, scheduled.title);
+/// }
+/// notifier.cancel_scheduled(&id).expect("Failed to cancel scheduled toast");
+/// ```
+#[derive(Clone)]
+pub struct ToastNotifier {
+    pub app_id: String,
+    image_cache: Option<Arc<ImageCache>>,
+    quiet_hours: Option<Arc<QuietHours>>,
+    rate_limit: Option<Arc<RateLimit>>,
+    digest: Option<Arc<Digest>>,
+    channels: Option<Arc<Channels>>,
+    middleware: Vec<Arc<dyn Middleware>>,
+    timeout: std::time::Duration,
+    load_profile: bool,
+    cancel: CancelHandle,
+}
+
+impl ToastNotifier {
+    /// Create a notifier for the App ID, PowerShell if `None`.
+    pub fn new(app_id: Option<&str>) -> Self {
+        Self {
+            app_id: app_id.unwrap_or(powershell::DEFAULT_APP_ID).to_string(),
+            image_cache: None,
+            quiet_hours: None,
+            rate_limit: None,
+            digest: None,
+            channels: None,
+            middleware: Vec::new(),
+            timeout: powershell::DEFAULT_TIMEOUT,
+            load_profile: false,
+            cancel: CancelHandle::default(),
+        }
+    }
+
+    /// Download remote images and store images set from bytes in this cache, see [ImageCache](struct.ImageCache.html).
+    pub fn set_image_cache(mut self, cache: ImageCache) -> Self {
+        self.image_cache = Some(Arc::new(cache));
+        self
+    }
+
+    /// Mute, downgrade or defer notifications during quiet hours, see [QuietHours](struct.QuietHours.html).
+    ///
+    /// The quiet hours apply to [show](#method.show). A deferred notification is scheduled, and its handle
+    /// refers to it once it is delivered.
+    ///
+    /// # Examples
+    /// ```
+    /// use win_toast_notify::{QuietAction, QuietHours, QuietWindow, TimeZone, ToastNotifier, Weekday, WinToastNotify};
+    ///
+    /// let notifier = ToastNotifier::new(None).set_quiet_hours(
+    ///     QuietHours::new(QuietAction::Downgrade)
+    ///         .add_window(QuietWindow::new(&Weekday::ALL, (22, 0), (7, 0)).expect("Invalid quiet window"))
+    ///         .set_time_zone(TimeZone::Local),
+    /// );
+    ///
+    /// let alert = WinToastNotify::new()
+    ///     .set_title("Disk almost full")
+    ///     .set_bypass_quiet_hours(true);
+    /// notifier.show(&alert).expect("Failed to show toast notification");
+    /// ```
+    pub fn set_quiet_hours(mut self, quiet_hours: QuietHours) -> Self {
+        self.quiet_hours = Some(Arc::new(quiet_hours));
+        self
+    }
+
+    /// Suppress duplicates and limit how many notifications are shown, see [RateLimit](struct.RateLimit.html).
+    ///
+    /// Clones of the notifier share the limit. Use [send](#method.send) to find out whether a notification was shown.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::time::Duration;
+    /// use win_toast_notify::{Delivery, RateLimit, ToastNotifier, WinToastNotify};
+    ///
+    /// let notifier = ToastNotifier::new(None).set_rate_limit(
+    ///     RateLimit::new()
+    ///         .set_duplicate_window(Duration::from_secs(300))
+    ///         .set_per_key_limit(3, Duration::from_secs(60)),
+    /// );
+    ///
+    /// let toast = WinToastNotify::new().set_title("db-1: replication lag above 30s");
+    /// match notifier.send(&toast).expect("Failed to show toast notification") {
+    ///     Delivery::Sent(_) => println!("Sent"),
+    ///     Delivery::Delayed(_, time) => println!("Delayed until {:?}", time),
+    ///     Delivery::Suppressed(reason) => println!("Suppressed: {}", reason),
+    ///     Delivery::Collapsed(_) => println!("Collapsed into a digest"),
+    /// }
+    /// ```
+    pub fn set_rate_limit(mut self, rate_limit: RateLimit) -> Self {
+        self.rate_limit = Some(Arc::new(rate_limit));
+        self
+    }
+
+    /// Collapse bursts of notifications into a digest, see [Digest](struct.Digest.html).
+    ///
+    /// The digest applies to [send](#method.send) after the rate limit, and the digest itself is subject to
+    /// the quiet hours. Clones of the notifier share the digest.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::time::Duration;
+    /// use win_toast_notify::{Digest, ToastNotifier, WinToastNotify};
+    ///
+    /// let notifier = ToastNotifier::new(None)
+    ///     .set_digest(Digest::new(5, Duration::from_secs(60)).set_label("alerts"));
+    ///
+    /// for host in ["web-1", "web-2", "web-3", "web-4", "web-5", "web-6", "web-7"] {
+    ///     let toast = WinToastNotify::new()
+    ///         .set_group("monitoring")
+    ///         .set_title(&format!("{} is unreachable", host));
+    ///     // The sixth and seventh are shown as "2 new alerts: 2 from monitoring"
+    ///     notifier.show(&toast).expect("Failed to show toast notification");
+    /// }
+    /// ```
+    pub fn set_digest(mut self, digest: Digest) -> Self {
+        self.digest = Some(Arc::new(digest));
+        self
+    }
+
+    /// Give notifications the defaults of their channel, and suppress those of disabled channels,
+    /// see [Channels](struct.Channels.html).
+    ///
+    /// The channels apply to [show](#method.show) and [send](#method.send). Sending a notification to a channel
+    /// that is not defined fails.
+    ///
+    /// # Examples
+    /// ```
+    /// use win_toast_notify::{Audio, Channel, Channels, Loop, Priority, Scenario, ToastNotifier, WinToastNotify};
+    ///
+    /// let channels = Channels::new()
+    ///     .add_channel(Channel::new("builds").set_audio(Audio::WinIM, Loop::False))
+    ///     .add_channel(Channel::new("alarms").set_scenario(Scenario::Alarm).set_priority(Priority::High))
+    ///     .load_config(r"C:\Users\Public\channels.ini")
+    ///     .expect("Invalid channel config");
+    /// let notifier = ToastNotifier::new(None).set_channels(channels);
+    ///
+    /// let toast = WinToastNotify::new().set_channel("builds").set_title("Build 42 passed");
+    /// notifier.show(&toast).expect("Failed to show toast notification");
+    /// ```
+    pub fn set_channels(mut self, channels: Channels) -> Self {
+        self.channels = Some(Arc::new(channels));
+        self
+    }
+
+    /// Add a hook that inspects, modifies or vetoes notifications before they are rendered, see
+    /// [Middleware](trait.Middleware.html).
+    ///
+    /// Hooks run in the order they were added, for notifications that are shown, replaced through a
+    /// [ToastHandle](struct.ToastHandle.html), scheduled, and for [progress updates](#method.progress_update).
+    /// They run after the channels, quiet hours, rate limit and digest, on the notification that is delivered.
+    /// A vetoed notification is [suppressed](enum.SuppressReason.html) by [send](#method.send), and fails otherwise.
+    ///
+    /// # Examples
+    /// ```
+    /// use win_toast_notify::{CropCircle, Middleware, Operation, ToastNotifier, WinToastNotify};
+    ///
+    /// struct Branding;
+    ///
+    /// impl Middleware for Branding {
+    ///     fn before(&self, toast: &mut WinToastNotify, _operation: Operation) -> Result<(), String> {
+    ///         if toast.logo.is_none() {
+    ///             toast.logo = Some(r"C:\Program Files\Contoso\logo.png".into());
+    ///         }
+    ///         Ok(())
+    ///     }
+    ///
+    ///     fn after(&self, toast: &WinToastNotify, operation: Operation, result: Result<(), &dyn std::error::Error>) {
+    ///         if let Err(e) = result {
+    ///             eprintln!("{:?} of {:?} failed: {}", operation, toast.title, e);
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// let notifier = ToastNotifier::new(None).add_middleware(Branding);
+    /// notifier
+    ///     .show(&WinToastNotify::new().set_title("Backup completed"))
+    ///     .expect("Failed to show toast notification");
+    /// ```
+    pub fn add_middleware(mut self, middleware: impl Middleware + 'static) -> Self {
+        self.middleware.push(Arc::new(middleware));
+        self
+    }
+
+    /// Kill PowerShell if an operation takes longer than `timeout`, 30 seconds by default.
+    ///
+    /// The operation then fails with [ProcessError::TimedOut](enum.ProcessError.html). For sound files, speech and
+    /// escalation, the timeout applies until the notification is shown. The timeout and the
+    /// [cancel handle](#method.cancel_handle) also apply to each process started on the way, such as to download
+    /// images, look up the time zone of the quiet hours or read the Windows build from the registry.
+    pub fn set_timeout(mut self, timeout: std::time::Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Load the PowerShell profile of the user before each operation. Default is `false`, which is faster and
+    /// avoids profiles that prompt or fail on locked-down machines.
+    pub fn set_load_profile(mut self, load_profile: bool) -> Self {
+        self.load_profile = load_profile;
+        self
+    }
+
+    /// A handle that cancels the operations of this notifier and its clones that are in flight,
+    /// such as from another thread when the user quits.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::time::Duration;
+    /// use win_toast_notify::{ProcessError, ToastNotifier, WinToastNotify};
+    ///
+    /// let notifier = ToastNotifier::new(None);
+    /// let cancel = notifier.cancel_handle();
+    /// std::thread::spawn(move || {
+    ///     std::thread::sleep(Duration::from_secs(1));
+    ///     cancel.cancel();
+    /// });
+    ///
+    /// if let Err(e) = notifier.show(&WinToastNotify::new().set_title("Sync finished")) {
+    ///     if e.downcast_ref::<ProcessError>() == Some(&ProcessError::Cancelled) {
+    ///         println!("Not shown within a second");
+    ///     }
+    /// }
+    /// ```
+    pub fn cancel_handle(&self) -> CancelHandle {
+        self.cancel.clone()
+    }
+
+    /// Show the notification now.
+    ///
+    /// A tag is generated for notifications without one, see [ToastHandle](struct.ToastHandle.html).
+    /// Fails if the notification is suppressed, see [send](#method.send).
+    pub fn show(&self, toast: &WinToastNotify) -> Result<ToastHandle, Box<dyn std::error::Error>> {
+        match self.send(toast)? {
+            Delivery::Sent(handle) | Delivery::Delayed(handle, _) | Delivery::Collapsed(handle) => Ok(handle),
+            Delivery::Suppressed(reason) => Err(format!("The notification was suppressed: {}", reason).into()),
+        }
+    }
+
+    /// Show the notification as its channel, the quiet hours, the rate limit and the digest allow,
+    /// and return what happened.
+    pub fn send(&self, toast: &WinToastNotify) -> Result<Delivery, Box<dyn std::error::Error>> {
+        let options = self.process_options();
+        let decided = match self.decide(toast, &options)? {
+            Decision::Deliver(decided) => decided,
+            Decision::Suppress(reason) => return Ok(Delivery::Suppressed(reason)),
+        };
+        let result = self.dispatch(&decided, &options);
+        if result.is_err() {
+            self.refund(&decided);
+        }
+        result
+    }
+
+    /// Apply the channel, the rate limit, the digest and the quiet hours to a notification.
+    ///
+    /// The notification is counted by the rate limit and the digest, see `refund` if it is not
+    /// delivered.
+    pub(crate) fn decide<'a>(
+        &self,
+        toast: &'a WinToastNotify,
+        options: &powershell::Options,
+    ) -> Result<Decision<'a>, Box<dyn std::error::Error>> {
+        let mut toast = Cow::Borrowed(toast);
+        if let (Some(channels), Some(name)) = (&self.channels, &toast.channel) {
+            let channel = channels.get(name).ok_or_else(|| format!("Unknown channel \"{}\"", name))?;
+            if !channel.enabled {
+                return Ok(Decision::Suppress(SuppressReason::ChannelDisabled));
+            }
+            channel.apply(toast.to_mut());
+        }
+        let mut delivery_time = None;
+        if let Some(rate_limit) = &self.rate_limit {
+            match rate_limit.check(&toast) {
+                RateDecision::Send => {}
+                RateDecision::Suppress(reason) => return Ok(Decision::Suppress(reason)),
+                RateDecision::Delay(time) => delivery_time = Some(time),
+            }
+        }
+        let mut decided = Box::new(Decided {
+            counted: toast.clone(),
+            tag: String::new(),
+            delivery_time,
+            collapsed: false,
+            toast,
+        });
+        if let Some(summary) = self.digest.as_ref().and_then(|digest| digest.collect(&decided.toast)) {
+            decided.toast = Cow::Owned(summary);
+            decided.collapsed = true;
+        }
+        decided.tag = decided.toast.effective_tag().map_or_else(unique_id, str::to_string);
+        if let Some(quiet_hours) = &self.quiet_hours {
+            match quiet_hours.apply(&mut decided.toast, options) {
+                Ok(quiet_until) => decided.delivery_time = decided.delivery_time.max(quiet_until),
+                Err(e) => {
+                    self.refund(&decided);
+                    return Err(e);
+                }
+            }
+        }
+        Ok(Decision::Deliver(decided))
+    }
+
+    /// Show or schedule a notification as decided. A notification vetoed by middleware is refunded.
+    pub(crate) fn dispatch(
+        &self,
+        decided: &Decided,
+        options: &powershell::Options,
+    ) -> Result<Delivery, Box<dyn std::error::Error>> {
+        let Decided { toast, tag, .. } = decided;
+        let handle = ToastHandle::new(self.clone(), tag.clone(), toast.group.clone());
+        // The delivery time may have passed while a queue retried
+        let delivery_time = decided.delivery_time.filter(|time| *time > SystemTime::now());
+        let result = match delivery_time {
+            Some(time) => self.schedule_as(toast, ScheduleOptions::new(time).set_tag(tag), options).map(|_| ()),
+            None => self.deliver(toast, tag, toast.group.as_deref(), options),
+        };
+        if let Err(e) = result {
+            return match e.downcast::<Veto>() {
+                Ok(veto) => {
+                    self.refund(decided);
+                    Ok(Delivery::Suppressed(SuppressReason::Vetoed(veto.0)))
+                }
+                Err(e) => Err(e),
+            };
+        }
+        Ok(match (decided.collapsed, delivery_time) {
+            (true, _) => Delivery::Collapsed(handle),
+            (false, Some(time)) => Delivery::Delayed(handle, time),
+            (false, None) => Delivery::Sent(handle),
+        })
+    }
+
+    /// Undo the counting of a notification by the rate limit, once it is not delivered.
+    pub(crate) fn refund(&self, decided: &Decided) {
+        if let Some(rate_limit) = &self.rate_limit {
+            rate_limit.refund(&decided.counted);
+        }
+    }
+
+    /// Show the notification with the given tag and group.
+    fn deliver(
+        &self,
+        toast: &WinToastNotify,
+        tag: &str,
+        group: Option<&str>,
+        options: &powershell::Options,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.with_middleware(toast, Operation::Show, |toast| self.deliver_now(toast, tag, group, options))
+    }
+
+    fn deliver_now(
+        &self,
+        toast: &WinToastNotify,
+        tag: &str,
+        group: Option<&str>,
+        options: &powershell::Options,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let toast = self.prepare(toast, options)?;
+        if toast.escalation.is_some() {
+            // Attempts are shown by a process that watches whether the notification is acknowledged
+            let mut command = self.notifier_script();
+            command.push_str(&toast.escalation_script(&self.app_id, tag, group, options)?);
+            powershell::spawn(&command, audio::SHOWN, options)?;
+            return Ok(());
+        }
+        let audio = toast.custom_audio()?;
+        let mut command = powershell::load_xml(&toast.to_xml_with(options)?);
+        command.push_str(&self.notifier_script());
+        command.push_str(&toast.notification_script(tag, group)?);
+        if toast.needs_helper() {
+            // Sound files and speech play in a helper process that lives as long as the notification
+            command.push_str(&toast.helper_script(audio.as_deref())?);
+            powershell::spawn(&command, audio::SHOWN, options)?;
+        } else {
+            command.push_str("$Notifier.Show($ToastNotification)\n");
+            powershell::run_with(&command, options)?;
+        }
+        Ok(())
+    }
+
+    /// Schedule the notification to be delivered by Windows at `delivery_time`.
+    ///
+    /// Returns the ID of the scheduled notification.
+    pub fn schedule(
+        &self,
+        toast: &WinToastNotify,
+        delivery_time: SystemTime,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        self.schedule_with(toast, ScheduleOptions::new(delivery_time))
+    }
+
+    /// Schedule the notification with an ID, tag/group and snooze settings.
+    ///
+    /// Returns the ID of the scheduled notification, generated if not set in `options`.
+    ///
+    /// # Note
+    /// Progress and custom audio need a running process, so they are not available for scheduled notifications.
+    /// Priority and expiring on reboot only apply to notifications shown immediately.
+    pub fn schedule_with(
+        &self,
+        toast: &WinToastNotify,
+        options: ScheduleOptions,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        self.schedule_as(toast, options, &self.process_options())
+    }
+
+    fn schedule_as(
+        &self,
+        toast: &WinToastNotify,
+        schedule: ScheduleOptions,
+        options: &powershell::Options,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let operation = Operation::Schedule(schedule.delivery_time);
+        self.with_middleware(toast, operation, |toast| self.schedule_now(toast, schedule, options))
+    }
+
+    fn schedule_now(
+        &self,
+        toast: &WinToastNotify,
+        schedule: ScheduleOptions,
+        options: &powershell::Options,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        if toast.progress.is_some() {
+            return Err("Notifications with progress cannot be scheduled".into());
+        }
+        if schedule.delivery_time <= SystemTime::now() {
+            return Err("The delivery time of a scheduled notification must be in the future".into());
+        }
+        let id = schedule.id.unwrap_or_else(unique_id);
+
+        let toast = self.prepare(toast, options)?;
+        let mut command = powershell::load_xml(&toast.to_xml_with(options)?);
+        command.push_str(&self.notifier_script());
+        writeln!(
+            command,
+            "$DeliveryTime = [DateTimeOffset]::FromUnixTimeMilliseconds({})",
+            unix_millis(schedule.delivery_time)
+        )?;
+        command.push_str("$ScheduledToast = [Windows.UI.Notifications.ScheduledToastNotification, Windows.UI.Notifications, ContentType = WindowsRuntime]::New($XmlDocument, $DeliveryTime");
+        if let Some((interval, count)) = schedule.snooze {
+            if !(60..=3600).contains(&interval.as_secs()) || !(1..=5).contains(&count) {
+                return Err("Snooze interval must be between 1 and 60 minutes, and the snooze count between 1 and 5".into());
+            }
+            write!(command, ", [TimeSpan]::FromSeconds({}), {}", interval.as_secs(), count)?;
+        }
+        command.push_str(")\n");
+        writeln!(command, "$ScheduledToast.Id = {}", powershell::quote(&id))?;
+        if let Some(tag) = schedule.tag.as_deref().or(toast.effective_tag()) {
+            writeln!(command, "$ScheduledToast.Tag = {}", powershell::quote(tag))?;
+        }
+        if let Some(group) = schedule.group.as_ref().or(toast.group.as_ref()) {
+            writeln!(command, "$ScheduledToast.Group = {}", powershell::quote(group))?;
+        }
+        command.push_str(&toast.properties_script("$ScheduledToast")?);
+        command.push_str("$Notifier.AddToSchedule($ScheduledToast)\n");
+        powershell::run_with(&command, options)?;
+        Ok(id)
+    }
+
+    /// Cancel a scheduled notification. Returns `false` if no notification has this ID.
+    pub fn cancel_scheduled(&self, id: &str) -> Result<bool, Box<dyn std::error::Error>> {
+        let mut command = self.notifier_script();
+        write!(
+            command,
+            r#"
+            foreach ($ScheduledToast in $Notifier.GetScheduledToastNotifications()) {{
+                if ($ScheduledToast.Id -eq {}) {{
+                    $Notifier.RemoveFromSchedule($ScheduledToast)
+                    'removed'
+                }}
+            }}
+            "#,
+            powershell::quote(id)
+        )?;
+        Ok(!self.run(&command)?.trim().is_empty())
+    }
+
+    /// List the notifications scheduled for this App ID.
+    pub fn list_scheduled(&self) -> Result<Vec<ScheduledToast>, Box<dyn std::error::Error>> {
+        let mut command = String::from(powershell::ENCODE_FUNCTION);
+        command.push_str(&self.notifier_script());
+        command.push_str(
+            r#"
+            foreach ($ScheduledToast in $Notifier.GetScheduledToastNotifications()) {
+                $Snooze = if ($null -ne $ScheduledToast.SnoozeInterval) { [int64]$ScheduledToast.SnoozeInterval.TotalSeconds } else { '' }
+                @(
+                    (Encode $ScheduledToast.Id),
+                    (Encode $ScheduledToast.Tag),
+                    (Encode $ScheduledToast.Group),
+                    $ScheduledToast.DeliveryTime.ToUnixTimeMilliseconds(),
+                    $Snooze,
+                    $ScheduledToast.MaximumSnoozeCount,
+                    (Encode $ScheduledToast.Content.GetXml())
+                ) -join "`t"
+            }
+            "#,
+        );
+        self.run(&command)?
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(ScheduledToast::parse)
+            .collect()
+    }
+
+    /// Update the progress of the notification with the tag, see
+    /// [WinToastNotify::set_progress](struct.WinToastNotify.html#method.set_progress).
+    pub fn progress_update(&self, tag: &str, value: f32, value_string: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.update_progress(Progress {
+            tag: tag.to_string(),
+            title: String::new(),
+            status: String::new(),
+            value,
+            value_string: value_string.to_string(),
+        })
+    }
+
+    /// Complete the progress of the notification with the tag, with a final status.
+    pub fn progress_complete(
+        &self,
+        tag: &str,
+        status: &str,
+        value_string: &str,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.update_progress(Progress {
+            tag: tag.to_string(),
+            title: String::new(),
+            status: status.to_string(),
+            value: 1.0,
+            value_string: value_string.to_string(),
+        })
+    }
+
+    /// Send the status, if any, the value and the value string of the progress.
+    fn update_progress(&self, progress: Progress) -> Result<(), Box<dyn std::error::Error>> {
+        let options = self.process_options();
+        let toast = WinToastNotify {
+            tag: Some(progress.tag.clone()),
+            progress: Some(progress),
+            ..WinToastNotify::new()
+        };
+        self.with_middleware(&toast, Operation::ProgressUpdate, |toast| {
+            let progress = toast.progress.as_ref().ok_or("The progress update has no progress")?;
+            let mut command =
+                String::from("$Dictionary = [System.Collections.Generic.Dictionary[String, String]]::New()\n");
+            if !progress.status.is_empty() {
+                writeln!(command, "$Dictionary.Add('progressStatus', {})", powershell::quote(&progress.status))?;
+            }
+            writeln!(command, "$Dictionary.Add('progressValue', '{}')", progress.value)?;
+            writeln!(
+                command,
+                "$Dictionary.Add('progressValueString', {})",
+                powershell::quote(&progress.value_string)
+            )?;
+            command.push_str("$NotificationData = [Windows.UI.Notifications.NotificationData]::New($Dictionary)\n");
+            command.push_str("$NotificationData.SequenceNumber = 2\n");
+            command.push_str(&self.notifier_script());
+            writeln!(command, "$Notifier.Update($NotificationData, {})", powershell::quote(&progress.tag))?;
+            powershell::run_with(&command, &options)?;
+            Ok(())
+        })
+    }
+
+    /// Run the middleware around an operation on the notification.
+    fn with_middleware<T>(
+        &self,
+        toast: &WinToastNotify,
+        operation: Operation,
+        run: impl FnOnce(&WinToastNotify) -> Result<T, Box<dyn std::error::Error>>,
+    ) -> Result<T, Box<dyn std::error::Error>> {
+        if self.middleware.is_empty() {
+            return run(toast);
+        }
+        let mut toast = toast.clone();
+        let veto = self.middleware.iter().find_map(|middleware| middleware.before(&mut toast, operation).err());
+        let result = match veto {
+            Some(reason) => Err(Veto(reason).into()),
+            None => run(&toast),
+        };
+        for middleware in &self.middleware {
+            middleware.after(&toast, operation, result.as_ref().map(|_| ()).map_err(|e| e.as_ref()));
+        }
+        result
+    }
+
+    /// The notification as it is rendered, with image variants selected and remote images replaced by cached files.
+    ///
+    /// Without a cache, images set from bytes are written by `to_xml`.
+    fn prepare<'a>(
+        &self,
+        toast: &'a WinToastNotify,
+        options: &powershell::Options,
+    ) -> Result<Cow<'a, WinToastNotify>, Box<dyn std::error::Error>> {
+        match &self.image_cache {
+            Some(cache) => {
+                let mut toast = toast.clone();
+                toast.resolve_variants(options);
+                cache.localize_toast(&mut toast, true, options)?;
+                Ok(Cow::Owned(toast))
+            }
+            None => Ok(Cow::Borrowed(toast)),
+        }
+    }
+
+    /// Run a script with the timeout, profile setting and cancellation of the notifier.
+    ///
+    /// Operations that start several processes capture `process_options` once instead, so that a cancellation
+    /// in between is not missed.
+    pub(crate) fn run(&self, script: &str) -> Result<String, Box<dyn std::error::Error>> {
+        powershell::run_with(script, &self.process_options())
+    }
+
+    pub(crate) fn process_options(&self) -> powershell::Options {
+        powershell::Options::new(self.timeout, self.load_profile, &self.cancel)
+    }
+
+    /// PowerShell that creates `$Notifier` for the App ID.
+    pub(crate) fn notifier_script(&self) -> String {
+        format!(
+            "$AppId = {}\n$Notifier = [Windows.UI.Notifications.ToastNotificationManager, Windows.UI.Notifications, ContentType = WindowsRuntime]::CreateToastNotifier($AppId)\n",
+            powershell::quote(&self.app_id)
+        )
+    }
+}
+
+/// Settings of a scheduled notification.
+pub struct ScheduleOptions {
+    pub delivery_time: SystemTime,
+    pub id: Option<String>,
+    pub tag: Option<String>,
+    pub group: Option<String>,
+    pub snooze: Option<(std::time::Duration, u32)>,
+}
+
+impl ScheduleOptions {
+    pub fn new(delivery_time: SystemTime) -> Self {
+        Self {
+            delivery_time,
+            id: None,
+            tag: None,
+            group: None,
+            snooze: None,
+        }
+    }
+
+    /// Set the ID used to cancel the notification later.
+    pub fn set_id(mut self, id: &str) -> Self {
+        self.id = Some(id.to_string());
+        self
+    }
+
+    /// Set the tag, instead of the tag of the notification.
+    pub fn set_tag(mut self, tag: &str) -> Self {
+        self.tag = Some(tag.to_string());
+        self
+    }
+
+    /// Set the group, instead of the group of the notification.
+    pub fn set_group(mut self, group: &str) -> Self {
+        self.group = Some(group.to_string());
+        self
+    }
+
+    /// Let the user snooze the notification.
+    ///
+    /// The interval must be between 1 and 60 minutes, and the count between 1 and 5.
+    pub fn set_snooze(mut self, interval: std::time::Duration, maximum_count: u32) -> Self {
+        self.snooze = Some((interval, maximum_count));
+        self
+    }
+}
+
+/// What `ToastNotifier::decide` decided for a notification.
+pub(crate) enum Decision<'a> {
+    Deliver(Box<Decided<'a>>),
+    Suppress(SuppressReason),
+}
+
+/// A notification as it is delivered, see `ToastNotifier::dispatch`.
+pub(crate) struct Decided<'a> {
+    pub(crate) toast: Cow<'a, WinToastNotify>,
+    /// The notification as counted by the rate limit.
+    counted: Cow<'a, WinToastNotify>,
+    tag: String,
+    delivery_time: Option<SystemTime>,
+    /// Whether the notification is a digest.
+    collapsed: bool,
+}
+
+impl Decided<'_> {
+    pub(crate) fn into_owned(self) -> Decided<'static> {
+        Decided {
+            toast: Cow::Owned(self.toast.into_owned()),
+            counted: Cow::Owned(self.counted.into_owned()),
+            tag: self.tag,
+            delivery_time: self.delivery_time,
+            collapsed: self.collapsed,
+        }
+    }
+}
+
+/// What [ToastNotifier::send](struct.ToastNotifier.html#method.send) did with a notification.
+pub enum Delivery {
+    /// Shown now.
+    Sent(ToastHandle),
+    /// Scheduled for the end of the quiet hours, or for when the rate limit allows.
+    /// The handle refers to the notification once it is delivered.
+    Delayed(ToastHandle, SystemTime),
+    /// Not shown.
+    Suppressed(SuppressReason),
+    /// Held in a digest, see [Digest](struct.Digest.html). The handle refers to the digest.
+    Collapsed(ToastHandle),
+}
+
+/// A notification waiting to be delivered, as returned by [ToastNotifier::list_scheduled](struct.ToastNotifier.html#method.list_scheduled).
+#[derive(Debug, Clone)]
+pub struct ScheduledToast {
+    pub id: String,
+    pub tag: Option<String>,
+    pub group: Option<String>,
+    pub delivery_time: SystemTime,
+    pub snooze_interval: Option<std::time::Duration>,
+    pub maximum_snooze_count: u32,
+    /// The first text element of the notification.
+    pub title: Option<String>,
+    /// The remaining text elements of the notification.
+    pub messages: Vec<String>,
+    /// The notification XML.
+    pub content: String,
+}
+
+impl ScheduledToast {
+    fn parse(line: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let fields: Vec<&str> = line.trim().split('\t').collect();
+        let [id, tag, group, delivery_time, snooze, count, content] = fields[..] else {
+            return Err(format!("Unexpected scheduled notification output: \"{}\"", line).into());
+        };
+        let content = powershell::decode(content)?;
+        let mut texts = content_texts(&content).into_iter();
+        Ok(Self {
+            id: powershell::decode(id)?,
+            tag: non_empty(powershell::decode(tag)?),
+            group: non_empty(powershell::decode(group)?),
+            delivery_time: UNIX_EPOCH + std::time::Duration::from_millis(delivery_time.parse()?),
+            snooze_interval: match snooze {
+                "" => None,
+                secs => Some(std::time::Duration::from_secs(secs.parse()?)),
+            },
+            maximum_snooze_count: count.parse()?,
+            title: texts.next(),
+            messages: texts.collect(),
+            content,
+        })
+    }
+}
+
+/// The text elements of a notification's XML, in document order.
+pub(crate) fn content_texts(xml: &str) -> Vec<String> {
+    let mut texts = Vec::new();
+    let mut current: Option<String> = None;
+    for event in EventReader::from_str(xml) {
+        match event {
+            Ok(XmlEvent::StartElement { name, .. }) if name.local_name == "text" => {
+                current = Some(String::new());
+            }
+            Ok(XmlEvent::Characters(s)) | Ok(XmlEvent::CData(s)) => {
+                if let Some(text) = current.as_mut() {
+                    text.push_str(&s);
+                }
+            }
+            Ok(XmlEvent::EndElement { name }) if name.local_name == "text" => {
+                texts.extend(current.take());
+            }
+            Ok(_) => {}
+            Err(_) => break,
+        }
+    }
+    texts
+}
+
+/// A short ID that is unique within this machine, usable as a notification tag or ID.
+pub(crate) fn unique_id() -> String {
+    static COUNTER: AtomicU32 = AtomicU32::new(0);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos() as u32)
+        .unwrap_or_default();
+    format!(
+        "{:08x}{:08x}",
+        nanos ^ std::process::id().rotate_left(16),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+pub(crate) fn unix_millis(time: SystemTime) -> u128 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or_default()
+}
+
+pub(crate) fn non_empty(s: String) -> Option<String> {
+    if s.is_empty() {
+        None
+    } else {
+        Some(s)
+    }
+}
@@ -0,0 +1,110 @@
+use std::os::windows::process::CommandExt;
+use std::process::Command;
+use std::sync::OnceLock;
+
+use crate::powershell::{self, Options};
+
+/// The Windows build notifications are rendered for.
+///
+/// Features that the build does not support are replaced by the closest supported alternative,
+/// e.g. `Scenario::Urgent` becomes `Scenario::Reminder`, a hero image becomes an inline image and a colored button
+/// a plain one.
+///
+/// # Examples
+/// ```
+/// use win_toast_notify::{WinToastNotify, Scenario, TargetPlatform, Capability};
+///
+/// let windows_10 = TargetPlatform::build(19045);
+/// assert!(!windows_10.supports(Capability::UrgentScenario));
+///
+/// let xml = WinToastNotify::new()
+///     .set_scenario(Scenario::Urgent)
+///     .set_target_platform(windows_10)
+///     .to_xml()
+///     .expect("Failed to render toast");
+/// assert!(xml.contains(r#"scenario="reminder""#));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TargetPlatform {
+    pub build: u32,
+}
+
+impl TargetPlatform {
+    pub const fn build(build: u32) -> Self {
+        Self { build }
+    }
+
+    /// The build of the running system, cached once it is read.
+    ///
+    /// If the build cannot be read, all features are assumed to be supported.
+    pub fn detect() -> Self {
+        Self::detect_with(&Options::default())
+    }
+
+    /// The build of the running system, read with the timeout and cancellation of a notifier.
+    pub(crate) fn detect_with(options: &Options) -> Self {
+        static DETECTED: OnceLock<TargetPlatform> = OnceLock::new();
+        if let Some(detected) = DETECTED.get() {
+            return *detected;
+        }
+        // A build that could not be read, such as on timeout, is read again next time
+        match current_build(options) {
+            Some(build) => *DETECTED.get_or_init(|| Self::build(build)),
+            None => Self::build(u32::MAX),
+        }
+    }
+
+    pub fn supports(&self, capability: Capability) -> bool {
+        self.build >= capability.min_build()
+    }
+}
+
+/// Notification features that depend on the Windows build.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Capability {
+    /// `ImagePlacement::Top`, Windows 10 1607.
+    HeroImage,
+    /// Progress bars, Windows 10 1703.
+    ProgressBar,
+    /// Headers that group notifications in Action Center, Windows 10 1703.
+    Header,
+    /// `Action::button_style`, Windows 11.
+    ButtonStyle,
+    /// `Scenario::Urgent`, Windows 11 build 22546.
+    UrgentScenario,
+}
+
+impl Capability {
+    /// The first Windows build supporting the feature.
+    pub fn min_build(&self) -> u32 {
+        match self {
+            Capability::HeroImage => 14393,
+            Capability::ProgressBar => 15063,
+            Capability::Header => 15063,
+            Capability::ButtonStyle => 22000,
+            Capability::UrgentScenario => 22546,
+        }
+    }
+}
+
+fn current_build(options: &Options) -> Option<u32> {
+    reg_value(r"HKLM\SOFTWARE\Microsoft\Windows NT\CurrentVersion", "CurrentBuildNumber", options)?
+        .parse()
+        .ok()
+}
+
+/// Read a registry value with `reg query`.
+///
+/// `REG_DWORD` values are returned in hex, such as `0x60`.
+pub(crate) fn reg_value(key: &str, name: &str, options: &Options) -> Option<String> {
+    let mut command = Command::new("reg");
+    command.creation_flags(0x08000000).args(["query", key, "/v", name]);
+    let output = powershell::run_command(&mut command, options).ok()?;
+    // "    CurrentBuildNumber    REG_SZ    22631"
+    String::from_utf8_lossy(&output)
+        .lines()
+        .find(|line| line.split_whitespace().next() == Some(name))?
+        .split_whitespace()
+        .nth(2)
+        .map(str::to_string)
+}
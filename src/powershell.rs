@@ -0,0 +1,387 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::io::{BufRead, BufReader, Read};
+use std::os::windows::process::CommandExt;
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, TryRecvError};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// App ID of Windows PowerShell, used when no App ID is set.
+pub(crate) const DEFAULT_APP_ID: &str =
+    r"{1AC14E77-02E7-4E5D-B744-2EB1AE5198B7}\WindowsPowerShell\v1.0\powershell.exe";
+
+/// How long PowerShell may run before it is killed, unless set with `ToastNotifier::set_timeout`.
+pub(crate) const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How often a running process is checked for its timeout and cancellation.
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// Defines `Encode`, which writes a string as hex encoded UTF-8 so that values can be
+/// printed on one line and split on tabs regardless of their content.
+pub(crate) const ENCODE_FUNCTION: &str = r#"
+function Encode($Value) {
+    if ($null -eq $Value) { return '' }
+    [BitConverter]::ToString([Text.Encoding]::UTF8.GetBytes([string]$Value)).Replace('-', '')
+}
+"#;
+
+/// The error of the PowerShell process that performs an operation.
+///
+/// Returned boxed by the operations of a [ToastNotifier](struct.ToastNotifier.html), use `downcast_ref` to tell
+/// a timeout from other errors.
+///
+/// # Examples
+/// ```
+/// use std::time::Duration;
+/// use win_toast_notify::{ProcessError, ToastNotifier, WinToastNotify};
+///
+/// let notifier = ToastNotifier::new(None).set_timeout(Duration::from_secs(5));
+/// if let Err(e) = notifier.show(&WinToastNotify::new().set_title("Hello")) {
+///     match e.downcast_ref::<ProcessError>() {
+///         Some(ProcessError::TimedOut(timeout)) => eprintln!("PowerShell hung for {:?}", timeout),
+///         _ => eprintln!("Failed to show toast notification: {}", e),
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProcessError {
+    /// PowerShell could not be started.
+    Start(String),
+    /// PowerShell failed with the error output.
+    Failed(String),
+    /// PowerShell did not finish within the timeout and was killed.
+    TimedOut(Duration),
+    /// PowerShell was killed by a [CancelHandle](struct.CancelHandle.html).
+    Cancelled,
+}
+
+impl fmt::Display for ProcessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProcessError::Start(message) | ProcessError::Failed(message) => f.write_str(message),
+            ProcessError::TimedOut(timeout) => write!(f, "PowerShell did not finish within {:?}", timeout),
+            ProcessError::Cancelled => write!(f, "PowerShell was cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for ProcessError {}
+
+/// Cancels the operations of a [ToastNotifier](struct.ToastNotifier.html) that are in flight, see
+/// [ToastNotifier::cancel_handle](struct.ToastNotifier.html#method.cancel_handle).
+#[derive(Debug, Clone, Default)]
+pub struct CancelHandle {
+    generation: Arc<AtomicU64>,
+}
+
+impl CancelHandle {
+    /// Kill the PowerShell processes of the operations in flight, which fail with `ProcessError::Cancelled`.
+    ///
+    /// Operations started afterwards are not affected, and notifications that are already shown stay.
+    pub fn cancel(&self) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn generation(&self) -> u64 {
+        self.generation.load(Ordering::SeqCst)
+    }
+}
+
+/// How PowerShell is run.
+#[derive(Debug, Clone)]
+pub(crate) struct Options {
+    pub(crate) timeout: Duration,
+    pub(crate) load_profile: bool,
+    /// The handle and its generation when the operation started.
+    pub(crate) cancel: Option<(CancelHandle, u64)>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            timeout: DEFAULT_TIMEOUT,
+            load_profile: false,
+            cancel: None,
+        }
+    }
+}
+
+impl Options {
+    pub(crate) fn new(timeout: Duration, load_profile: bool, cancel: &CancelHandle) -> Self {
+        Self {
+            timeout,
+            load_profile,
+            cancel: Some((cancel.clone(), cancel.generation())),
+        }
+    }
+
+    fn command(&self, script: &str) -> Command {
+        let mut command = Command::new("powershell");
+        command.creation_flags(0x08000000);
+        if !self.load_profile {
+            command.arg("-NoProfile");
+        }
+        command
+            .args(["-NonInteractive", "-Command", script])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        command
+    }
+
+    fn cancelled(&self) -> bool {
+        self.cancel
+            .as_ref()
+            .is_some_and(|(handle, generation)| handle.generation() != *generation)
+    }
+}
+
+/// Run a script with PowerShell, killing it on timeout or cancellation, and return its standard output.
+pub(crate) fn run_with(script: &str, options: &Options) -> Result<String, Box<dyn std::error::Error>> {
+    let stdout = run_command(&mut options.command(script), options)?;
+    Ok(String::from_utf8_lossy(&stdout).into_owned())
+}
+
+/// Run a command, killing it on timeout or cancellation, and return its standard output.
+pub(crate) fn run_command(command: &mut Command, options: &Options) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| ProcessError::Start(format!("Failed to execute process: {}", e)))?;
+    let stdout = read_in_background(child.stdout.take());
+    let stderr = read_in_background(child.stderr.take());
+    let status = wait(&mut child, options, Child::try_wait)?;
+    let stdout = stdout.join().unwrap_or_default();
+    let stderr = stderr.join().unwrap_or_default();
+    if !status.success() {
+        return Err(ProcessError::Failed(format!(
+            "Failed to execute command: {}",
+            String::from_utf8_lossy(&stderr)
+        ))
+        .into());
+    }
+
+    Ok(stdout)
+}
+
+/// Start a script with PowerShell that keeps running after it printed the line `ready`.
+///
+/// Returns once `ready` is printed, or with an error if the script exits before. The timeout and cancellation
+/// apply until then.
+pub(crate) fn spawn(script: &str, ready: &str, options: &Options) -> Result<(), Box<dyn std::error::Error>> {
+    let mut child = options
+        .command(script)
+        .spawn()
+        .map_err(|e| ProcessError::Start(format!("Failed to execute process: {}", e)))?;
+    // Read from the start, so that the script does not block on a full pipe before `ready`
+    let stderr = read_in_background(child.stderr.take());
+    let (sender, receiver) = mpsc::channel();
+    if let Some(stdout) = child.stdout.take() {
+        let ready = ready.to_string();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                if line.trim() == ready {
+                    let _ = sender.send(());
+                    return;
+                }
+            }
+        });
+    }
+    // Disconnected once standard output is closed without `ready`
+    let shown = wait(&mut child, options, |_| match receiver.try_recv() {
+        Ok(()) => Ok(Some(true)),
+        Err(TryRecvError::Empty) => Ok(None),
+        Err(TryRecvError::Disconnected) => Ok(Some(false)),
+    })?;
+    if shown {
+        return Ok(());
+    }
+    wait(&mut child, options, Child::try_wait)?;
+    Err(ProcessError::Failed(format!(
+        "Failed to execute command: {}",
+        String::from_utf8_lossy(&stderr.join().unwrap_or_default())
+    ))
+    .into())
+}
+
+/// Poll `done` until it returns a value, killing the child on timeout or cancellation.
+fn wait<T>(
+    child: &mut Child,
+    options: &Options,
+    mut done: impl FnMut(&mut Child) -> std::io::Result<Option<T>>,
+) -> Result<T, ProcessError> {
+    let deadline = Instant::now().checked_add(options.timeout);
+    loop {
+        if let Some(value) = done(child).map_err(|e| ProcessError::Failed(format!("Failed to execute process: {}", e)))? {
+            return Ok(value);
+        }
+        let error = if options.cancelled() {
+            ProcessError::Cancelled
+        } else if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            ProcessError::TimedOut(options.timeout)
+        } else {
+            thread::sleep(POLL_INTERVAL);
+            continue;
+        };
+        let _ = child.kill();
+        let _ = child.wait();
+        return Err(error);
+    }
+}
+
+/// Read a pipe to the end on another thread.
+fn read_in_background(pipe: Option<impl Read + Send + 'static>) -> JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut bytes = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut bytes);
+        }
+        bytes
+    })
+}
+
+/// Quote a string as a PowerShell literal.
+pub(crate) fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+/// Decode a value written by `Encode`.
+pub(crate) fn decode(hex: &str) -> Result<String, Box<dyn std::error::Error>> {
+    let hex = hex.trim();
+    if hex.len() % 2 == 1 || !hex.is_ascii() {
+        return Err(format!("Invalid encoded value: \"{}\"", hex).into());
+    }
+    let bytes = (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16))
+        .collect::<Result<Vec<u8>, _>>()
+        .map_err(|e| format!("Invalid encoded value: {}", e))?;
+    Ok(String::from_utf8(bytes)?)
+}
+
+/// Script that loads the toast XML into `$XmlDocument`.
+///
+/// The XML is placed in an expandable here-string with `$` and `` ` `` escaped, so that it is loaded as it is.
+pub(crate) fn load_xml(xml: &str) -> String {
+    // The terminator ("@") cannot be preceded by a space
+    format!(
+        "$xml = @\"\n{}\n\"@\n$XmlDocument = [Windows.Data.Xml.Dom.XmlDocument, Windows.Data.Xml.Dom.XmlDocument, ContentType = WindowsRuntime]::New()\n$XmlDocument.loadXml($xml)\n",
+        escape_expandable(xml)
+    )
+}
+
+/// Escape `s` for an expandable string, so that nothing is expanded.
+fn escape_expandable(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, '`' | '$') {
+            escaped.push('`');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Expand environment variables written as in PowerShell, such as `$env:USERPROFILE\Downloads`.
+///
+/// Variables that are not set are replaced by nothing, as PowerShell does.
+pub(crate) fn expand_env(s: &str) -> Cow<'_, str> {
+    if !s.to_ascii_lowercase().contains("$env:") {
+        return Cow::Borrowed(s);
+    }
+    let mut expanded = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(i) = rest.to_ascii_lowercase().find("$env:") {
+        expanded.push_str(&rest[..i]);
+        let name_start = i + "$env:".len();
+        let name_len = rest[name_start..]
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len() - name_start);
+        let name = &rest[name_start..name_start + name_len];
+        match name.is_empty() {
+            true => expanded.push_str(&rest[i..name_start]),
+            false => expanded.push_str(&std::env::var(name).unwrap_or_default()),
+        }
+        rest = &rest[name_start + name_len..];
+    }
+    expanded.push_str(rest);
+    Cow::Owned(expanded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Action, ActivationType, ToastArguments, WinToastNotify};
+
+    /// The value of the here-string in a `load_xml` script, as PowerShell reads it.
+    ///
+    /// Fails on anything PowerShell would expand: a `$` that is not escaped by a backtick.
+    fn here_string(script: &str) -> Result<String, String> {
+        let body = script
+            .strip_prefix("$xml = @\"\n")
+            .and_then(|rest| rest.split_once("\n\"@\n"))
+            .ok_or("Not a here-string")?
+            .0;
+        let mut value = String::new();
+        let mut chars = body.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '`' => value.push(chars.next().ok_or("Trailing backtick")?),
+                '$' if chars.peek().is_some_and(|c| c.is_alphanumeric() || "_{(:?".contains(*c)) => {
+                    return Err(format!("Expanded: {}", body));
+                }
+                _ => value.push(c),
+            }
+        }
+        Ok(value)
+    }
+
+    #[test]
+    fn loads_xml_as_it_is() {
+        let arguments = ToastArguments::new()
+            .add("price", "$5")
+            .add("note", "`$(calc) $env:TEMP ${env:TEMP} $true")
+            .encode()
+            .unwrap();
+        let toast = WinToastNotify::new()
+            .set_title("Pay $5 `now`")
+            .set_messages(vec!["$(Remove-Item C:\\)", "$env:USERPROFILE"])
+            .set_actions(vec![Action {
+                activation_type: ActivationType::Foreground,
+                action_content: "Buy".to_string(),
+                arguments,
+                image_url: None,
+                button_style: None,
+            }]);
+        let xml = toast.to_xml().unwrap();
+        assert!(xml.contains("$env:USERPROFILE") && xml.contains("$env:TEMP"));
+        assert_eq!(here_string(&load_xml(&xml)).unwrap(), xml);
+    }
+
+    #[test]
+    fn expands_env() {
+        std::env::set_var("WIN_TOAST_NOTIFY_TEST", r"C:\Users\Test");
+        assert_eq!(expand_env(r"$env:WIN_TOAST_NOTIFY_TEST\Downloads"), r"C:\Users\Test\Downloads");
+        assert_eq!(expand_env(r"$ENV:win_toast_notify_test"), if cfg!(windows) { r"C:\Users\Test" } else { "" });
+        assert_eq!(expand_env("$env:WIN_TOAST_NOTIFY_UNSET/a"), "/a");
+        assert_eq!(expand_env("$5 and $env: alone"), "$5 and $env: alone");
+
+        let toast = WinToastNotify::new()
+            .set_open(r"$env:WIN_TOAST_NOTIFY_TEST\report.pdf")
+            .set_actions(vec![Action {
+                activation_type: ActivationType::Protocol,
+                action_content: "Folder".to_string(),
+                arguments: r"$env:WIN_TOAST_NOTIFY_TEST\Downloads".to_string(),
+                image_url: None,
+                button_style: None,
+            }]);
+        let xml = toast.to_xml().unwrap();
+        assert!(xml.contains(r#"launch="C:\Users\Test\report.pdf""#));
+        assert!(xml.contains(r#"arguments="C:\Users\Test\Downloads""#));
+    }
+}
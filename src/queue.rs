@@ -0,0 +1,497 @@
+use std::any::Any;
+use std::collections::{HashSet, VecDeque};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::notifier::{Decided, Decision};
+use crate::{ProcessError, ToastNotifier, WinToastNotify};
+
+/// Shows notifications on a background thread, so that the caller does not wait for PowerShell.
+///
+/// Notifications are sent as by [ToastNotifier::send](struct.ToastNotifier.html#method.send) in the order they
+/// were enqueued. If PowerShell times out or cannot be started, the delivery is retried with exponential backoff,
+/// 3 times after 0.5, 1 and 2 seconds by default; notifications with the same tag wait for it, others do not.
+/// A retry is not counted again by the rate limit or the digest. Other errors, such as a script that fails,
+/// a missing image or a [cancellation](struct.CancelHandle.html), are not retried. A panic in middleware, a clock or
+/// an image fetcher fails the notification like an error.
+///
+/// Call [shutdown](#method.shutdown) before exiting to deliver the notifications still in the queue.
+///
+/// # Examples
+/// ```
+/// use std::time::Duration;
+/// use win_toast_notify::{ToastNotifier, ToastQueue, WinToastNotify};
+///
+/// let queue = ToastQueue::new(ToastNotifier::new(None))
+///     .set_retries(5, Duration::from_secs(1))
+///     .set_on_failure(|toast, e| eprintln!("Failed to show {:?}: {}", toast.title, e));
+///
+/// for step in ["Downloading", "Installing", "Done"] {
+///     // Returns immediately
+///     queue
+///         .enqueue(WinToastNotify::new().set_tag("update").set_title(step))
+///         .expect("The queue is shut down");
+/// }
+///
+/// if !queue.shutdown(Duration::from_secs(10)) {
+///     eprintln!("Some notifications were not shown");
+/// }
+/// ```
+pub struct ToastQueue {
+    shared: Arc<Shared>,
+    worker: Mutex<Option<JoinHandle<()>>>,
+}
+
+struct Shared {
+    state: Mutex<State>,
+    changed: Condvar,
+}
+
+struct State {
+    pending: VecDeque<Job>,
+    /// Whether the worker is sending a notification.
+    sending: bool,
+    /// No more notifications are accepted, the worker stops once the queue is empty.
+    closed: bool,
+    /// The worker stops after the notification it is sending.
+    stopped: bool,
+    retries: u32,
+    backoff: Duration,
+    on_failure: Option<Arc<FailureHandler>>,
+}
+
+type FailureHandler = dyn Fn(&WinToastNotify, &dyn std::error::Error) + Send + Sync;
+
+struct Job {
+    toast: WinToastNotify,
+    /// The notification as delivered, once decided by the first attempt.
+    decided: Option<Box<Decided<'static>>>,
+    /// How many times sending failed.
+    failures: u32,
+    not_before: Instant,
+}
+
+impl ToastQueue {
+    /// Start a worker thread that sends notifications with the notifier.
+    pub fn new(notifier: ToastNotifier) -> Self {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                pending: VecDeque::new(),
+                sending: false,
+                closed: false,
+                stopped: false,
+                retries: 3,
+                backoff: Duration::from_millis(500),
+                on_failure: None,
+            }),
+            changed: Condvar::new(),
+        });
+        let worker = {
+            let shared = shared.clone();
+            thread::spawn(move || shared.work(&notifier))
+        };
+        Self {
+            shared,
+            worker: Mutex::new(Some(worker)),
+        }
+    }
+
+    /// Retry notifications up to `retries` times when PowerShell times out or cannot be started, first after `backoff`,
+    /// then doubling the wait each time.
+    pub fn set_retries(self, retries: u32, backoff: Duration) -> Self {
+        {
+            let mut state = self.shared.lock();
+            state.retries = retries;
+            state.backoff = backoff;
+        }
+        self
+    }
+
+    /// Call `on_failure` on the worker thread for notifications that could not be shown.
+    pub fn set_on_failure(
+        self,
+        on_failure: impl Fn(&WinToastNotify, &dyn std::error::Error) + Send + Sync + 'static,
+    ) -> Self {
+        self.shared.lock().on_failure = Some(Arc::new(on_failure));
+        self
+    }
+
+    /// Add a notification to the queue. Fails if the queue is shut down.
+    pub fn enqueue(&self, toast: WinToastNotify) -> Result<(), Box<dyn std::error::Error>> {
+        let mut state = self.shared.lock();
+        if state.closed || state.stopped {
+            return Err("The notification queue is shut down".into());
+        }
+        state.pending.push_back(Job {
+            toast,
+            decided: None,
+            failures: 0,
+            not_before: Instant::now(),
+        });
+        self.shared.changed.notify_all();
+        Ok(())
+    }
+
+    /// The number of notifications waiting to be shown, including the one being shown.
+    pub fn len(&self) -> usize {
+        let state = self.shared.lock();
+        state.pending.len() + state.sending as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Wait until the notifications in the queue, and those enqueued meanwhile, are shown or failed.
+    pub fn flush(&self) {
+        let mut state = self.shared.lock();
+        while !state.stopped && (state.sending || !state.pending.is_empty()) {
+            state = self.shared.changed.wait(state).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    /// Stop accepting notifications and wait up to `timeout` for the queue to be delivered, `Duration::MAX` waits
+    /// as long as it takes.
+    ///
+    /// Returns `false` if notifications were left in the queue. They are dropped, and the worker stops after the
+    /// notification it is showing.
+    pub fn shutdown(&self, timeout: Duration) -> bool {
+        // None waits without a deadline
+        let deadline = Instant::now().checked_add(timeout);
+        let mut state = self.shared.lock();
+        state.closed = true;
+        self.shared.changed.notify_all();
+        while !state.stopped && (state.sending || !state.pending.is_empty()) {
+            let Some(deadline) = deadline else {
+                state = self.shared.changed.wait(state).unwrap_or_else(|e| e.into_inner());
+                continue;
+            };
+            let now = Instant::now();
+            if now >= deadline {
+                state.stopped = true;
+                state.pending.clear();
+                self.shared.changed.notify_all();
+                return false;
+            }
+            state = match self.shared.changed.wait_timeout(state, deadline - now) {
+                Ok((state, _)) => state,
+                Err(e) => e.into_inner().0,
+            };
+        }
+        // Left behind by a worker that panicked
+        let delivered = state.pending.is_empty();
+        drop(state);
+        if let Some(worker) = self.worker.lock().unwrap_or_else(|e| e.into_inner()).take() {
+            let _ = worker.join();
+        }
+        delivered
+    }
+}
+
+impl Drop for ToastQueue {
+    // The worker delivers the rest of the queue in the background
+    fn drop(&mut self) {
+        self.shared.lock().closed = true;
+        self.shared.changed.notify_all();
+    }
+}
+
+/// What the worker does next.
+enum Next {
+    Send(usize),
+    WaitUntil(Instant),
+    Wait,
+    Stop,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn work(&self, notifier: &ToastNotifier) {
+        let _stopping = Stopping(self);
+        let mut state = self.lock();
+        loop {
+            state = match state.next() {
+                Next::Stop => break,
+                Next::Wait => self.changed.wait(state).unwrap_or_else(|e| e.into_inner()),
+                Next::WaitUntil(time) => {
+                    let timeout = time.saturating_duration_since(Instant::now());
+                    match self.changed.wait_timeout(state, timeout) {
+                        Ok((state, _)) => state,
+                        Err(e) => e.into_inner().0,
+                    }
+                }
+                Next::Send(index) => {
+                    let Some(mut job) = state.pending.remove(index) else {
+                        continue;
+                    };
+                    state.sending = true;
+                    let (retries, backoff, on_failure) = (state.retries, state.backoff, state.on_failure.clone());
+                    drop(state);
+
+                    // Middleware, clocks and image fetchers are user code, a panic fails the notification
+                    let result = panic::catch_unwind(AssertUnwindSafe(|| attempt(notifier, &mut job)))
+                        .unwrap_or_else(|payload| Err(panicked(payload).into()));
+                    let retry = match &result {
+                        Err(e) if should_retry(&job, e.as_ref(), retries) => true,
+                        Err(e) => {
+                            refund(notifier, &job);
+                            if let Some(on_failure) = on_failure {
+                                let _ = panic::catch_unwind(AssertUnwindSafe(|| on_failure(&job.toast, e.as_ref())));
+                            }
+                            false
+                        }
+                        Ok(()) => false,
+                    };
+
+                    let mut state = self.lock();
+                    state.sending = false;
+                    if retry && state.stopped {
+                        refund(notifier, &job);
+                    } else if retry {
+                        state.retry(index, job, backoff);
+                    }
+                    self.changed.notify_all();
+                    state
+                }
+            }
+        }
+    }
+}
+
+/// Marks the queue stopped when the worker exits, even by panicking, so that nothing waits for it.
+struct Stopping<'a>(&'a Shared);
+
+impl Drop for Stopping<'_> {
+    fn drop(&mut self) {
+        let mut state = self.0.lock();
+        state.sending = false;
+        state.stopped = true;
+        self.0.changed.notify_all();
+    }
+}
+
+/// Decide a notification on its first attempt, and deliver it.
+fn attempt(notifier: &ToastNotifier, job: &mut Job) -> Result<(), Box<dyn std::error::Error>> {
+    let options = notifier.process_options();
+    let decided = match job.decided.take() {
+        Some(decided) => decided,
+        None => match notifier.decide(&job.toast, &options)? {
+            Decision::Deliver(decided) => Box::new(decided.into_owned()),
+            Decision::Suppress(_) => return Ok(()),
+        },
+    };
+    // Retries only deliver again
+    let decided = job.decided.insert(decided);
+    notifier.dispatch(decided, &options).map(|_| ())
+}
+
+/// Undo the rate limit of a notification that is not shown.
+fn refund(notifier: &ToastNotifier, job: &Job) {
+    if let Some(decided) = &job.decided {
+        notifier.refund(decided);
+    }
+}
+
+/// Whether to send a notification again after it failed with `error`.
+fn should_retry(job: &Job, error: &(dyn std::error::Error + 'static), retries: u32) -> bool {
+    is_transient(error) && job.decided.is_some() && job.failures < retries
+}
+
+fn panicked(payload: Box<dyn Any + Send>) -> String {
+    let message = payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+        .unwrap_or("Box<dyn Any>");
+    format!("Sending the notification panicked: {}", message)
+}
+
+/// Whether an error may not happen again, i.e. PowerShell timed out or could not be started.
+///
+/// A script that failed would fail the same way again.
+fn is_transient(error: &(dyn std::error::Error + 'static)) -> bool {
+    matches!(
+        error.downcast_ref::<ProcessError>(),
+        Some(ProcessError::Start(_) | ProcessError::TimedOut(_))
+    )
+}
+
+impl State {
+    /// Put a notification that failed back where it was, to be sent again after the backoff.
+    fn retry(&mut self, index: usize, mut job: Job, backoff: Duration) {
+        job.not_before = Instant::now() + backoff.saturating_mul(2u32.saturating_pow(job.failures));
+        job.failures += 1;
+        // Notifications enqueued meanwhile are behind it
+        let index = index.min(self.pending.len());
+        self.pending.insert(index, job);
+    }
+
+    /// The first notification that is due and not behind one with the same tag, or how long to wait.
+    fn next(&self) -> Next {
+        if self.stopped || (self.closed && self.pending.is_empty()) {
+            return Next::Stop;
+        }
+        let now = Instant::now();
+        let mut waiting_tags = HashSet::new();
+        let mut wake = None;
+        for (index, job) in self.pending.iter().enumerate() {
+            let tag = job.toast.effective_tag();
+            if tag.is_some_and(|tag| waiting_tags.contains(tag)) {
+                continue;
+            }
+            if job.not_before <= now {
+                return Next::Send(index);
+            }
+            wake = Some(wake.map_or(job.not_before, |wake: Instant| wake.min(job.not_before)));
+            if let Some(tag) = tag {
+                waiting_tags.insert(tag);
+            }
+        }
+        match wake {
+            Some(time) => Next::WaitUntil(time),
+            None => Next::Wait,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use super::*;
+    use crate::{RateLimit, SuppressReason};
+
+    fn job(tag: Option<&str>, not_before: Instant) -> Job {
+        let mut toast = WinToastNotify::new();
+        if let Some(tag) = tag {
+            toast = toast.set_tag(tag);
+        }
+        Job {
+            toast,
+            decided: None,
+            failures: 0,
+            not_before,
+        }
+    }
+
+    fn state(pending: Vec<Job>) -> State {
+        State {
+            pending: pending.into(),
+            sending: false,
+            closed: false,
+            stopped: false,
+            retries: 3,
+            backoff: Duration::from_millis(500),
+            on_failure: None,
+        }
+    }
+
+    #[test]
+    fn next_keeps_tags_in_order() {
+        let now = Instant::now();
+        let later = now + Duration::from_secs(60);
+        let state = state(vec![job(Some("a"), later), job(Some("a"), now), job(Some("b"), now)]);
+        assert!(matches!(state.next(), Next::Send(2)));
+
+        let state = self::state(vec![job(Some("a"), later), job(Some("a"), now)]);
+        assert!(matches!(state.next(), Next::WaitUntil(time) if time == later));
+
+        let state = self::state(vec![job(None, later), job(None, now)]);
+        assert!(matches!(state.next(), Next::Send(1)));
+    }
+
+    #[test]
+    fn next_waits_and_stops() {
+        let mut state = state(Vec::new());
+        assert!(matches!(state.next(), Next::Wait));
+        state.pending.push_back(job(None, Instant::now()));
+        state.closed = true;
+        assert!(matches!(state.next(), Next::Send(0)));
+        state.pending.clear();
+        assert!(matches!(state.next(), Next::Stop));
+
+        let mut state = self::state(vec![job(None, Instant::now())]);
+        state.stopped = true;
+        assert!(matches!(state.next(), Next::Stop));
+    }
+
+    #[test]
+    fn retry_blocks_the_same_tag() {
+        let now = Instant::now();
+        let mut state = state(vec![job(Some("a"), now), job(Some("b"), now)]);
+        let Next::Send(index) = state.next() else {
+            panic!("Expected a notification to send");
+        };
+        let failed = state.pending.remove(index).unwrap();
+        state.pending.push_back(job(Some("a"), now));
+        state.retry(index, failed, Duration::from_secs(60));
+
+        assert_eq!(state.pending[0].failures, 1);
+        assert!(state.pending[0].not_before >= now + Duration::from_secs(60));
+        // The other tag goes first, the same tag waits for the retry
+        assert!(matches!(state.next(), Next::Send(1)));
+        state.pending.remove(1);
+        assert!(matches!(state.next(), Next::WaitUntil(_)));
+    }
+
+    #[test]
+    fn retries_only_transient_errors() {
+        let notifier = ToastNotifier::new(None);
+        let toast = WinToastNotify::new().set_title("Retry");
+        let mut job = job(None, Instant::now());
+        let Decision::Deliver(decided) = notifier.decide(&toast, &notifier.process_options()).unwrap() else {
+            panic!("Expected the notification to be delivered");
+        };
+        job.decided = Some(Box::new(decided.into_owned()));
+
+        let start = ProcessError::Start("Not found".to_string());
+        let timed_out = ProcessError::TimedOut(Duration::from_secs(30));
+        assert!(should_retry(&job, &start, 3));
+        assert!(should_retry(&job, &timed_out, 3));
+        assert!(!should_retry(&job, &ProcessError::Failed("Bad XML".to_string()), 3));
+        assert!(!should_retry(&job, &ProcessError::Cancelled, 3));
+        let other: Box<dyn std::error::Error> = "Missing image".into();
+        assert!(!should_retry(&job, other.as_ref(), 3));
+
+        job.failures = 3;
+        assert!(!should_retry(&job, &start, 3));
+        // Not decided, e.g. a channel that does not exist
+        job.failures = 0;
+        job.decided = None;
+        assert!(!should_retry(&job, &start, 3));
+    }
+
+    #[test]
+    fn refunds_dropped_notifications() {
+        let now = SystemTime::now();
+        let notifier = ToastNotifier::new(None).set_rate_limit(
+            RateLimit::new()
+                .set_duplicate_window(Duration::from_secs(60))
+                .set_clock(move || now),
+        );
+        let toast = WinToastNotify::new().set_title("Dropped");
+        let Decision::Deliver(decided) = notifier.decide(&toast, &notifier.process_options()).unwrap() else {
+            panic!("Expected the notification to be delivered");
+        };
+        assert!(matches!(
+            notifier.decide(&toast, &notifier.process_options()).unwrap(),
+            Decision::Suppress(SuppressReason::Duplicate)
+        ));
+
+        let mut job = job(None, Instant::now());
+        job.decided = Some(Box::new(decided.into_owned()));
+        refund(&notifier, &job);
+        assert!(matches!(notifier.decide(&toast, &notifier.process_options()).unwrap(), Decision::Deliver(_)));
+    }
+
+    #[test]
+    fn reports_panics() {
+        let payload = panic::catch_unwind(|| panic!("Clock failed")).unwrap_err();
+        assert_eq!(panicked(payload), "Sending the notification panicked: Clock failed");
+    }
+}
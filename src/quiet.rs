@@ -0,0 +1,430 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::{powershell, Audio, Duration, Loop, Priority, Scenario, WinToastNotify};
+
+/// Times during which a [ToastNotifier](struct.ToastNotifier.html) holds back notifications.
+///
+/// Windows are weekly, in a time zone. A window whose end is not after its start runs past midnight,
+/// e.g. from 22:00 to 07:00 the next day, and a window from a time to the same time lasts a whole day.
+///
+/// Notifications set with [set_bypass_quiet_hours](struct.WinToastNotify.html#method.set_bypass_quiet_hours)
+/// are shown as they are.
+///
+/// # Examples
+/// ```
+/// use std::time::{Duration, SystemTime, UNIX_EPOCH};
+/// use win_toast_notify::{QuietAction, QuietHours, QuietWindow, TimeZone, Weekday};
+///
+/// // Monday 2024-01-01 23:30 in UTC+1
+/// let now = UNIX_EPOCH + Duration::from_secs(1704148200);
+///
+/// let quiet = QuietHours::new(QuietAction::Defer)
+///     .add_window(QuietWindow::new(&Weekday::WORKDAYS, (22, 0), (7, 0)).expect("Invalid quiet window"))
+///     .set_time_zone(TimeZone::Fixed(60))
+///     .set_clock(move || now);
+///
+/// // Until Tuesday 07:00 in UTC+1
+/// let end = UNIX_EPOCH + Duration::from_secs(1704175200);
+/// assert_eq!(quiet.active_until().unwrap(), Some(end));
+///
+/// assert!(QuietWindow::new(&Weekday::ALL, (24, 0), (7, 0)).is_err());
+/// ```
+pub struct QuietHours {
+    windows: Vec<QuietWindow>,
+    action: QuietAction,
+    time_zone: TimeZone,
+    clock: Box<dyn Clock>,
+    /// The offsets of a named or local time zone, cached per hour.
+    offsets: Mutex<HashMap<i64, i32>>,
+}
+
+/// What happens to notifications during quiet hours.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QuietAction {
+    /// Show them without sound, and without [escalating](struct.Escalation.html) them.
+    Mute,
+    /// Show them without sound as normal notifications: the scenario becomes `Scenario::None`,
+    /// the duration `Duration::Short` and the priority `Priority::Default`, and they are not escalated.
+    Downgrade,
+    /// Schedule them for the end of the quiet hours. Notifications with progress are muted instead,
+    /// since they cannot be scheduled.
+    Defer,
+}
+
+/// A weekly time window, see [QuietHours](struct.QuietHours.html).
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuietWindow {
+    /// The days the window starts on.
+    pub days: Vec<Weekday>,
+    /// Hour and minute.
+    pub start: (u8, u8),
+    /// Hour and minute.
+    pub end: (u8, u8),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Weekday {
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday,
+}
+
+/// The time zone of the quiet hours.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TimeZone {
+    Utc,
+    /// A fixed offset from UTC in minutes, such as `-300` for UTC-5.
+    Fixed(i32),
+    /// The time zone of the system, including daylight saving time.
+    Local,
+    /// A Windows time zone ID, such as `W. Europe Standard Time`, including daylight saving time.
+    Named(String),
+}
+
+/// The current time, replaceable for tests.
+///
+/// Implemented for closures returning a `SystemTime`.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> SystemTime;
+}
+
+impl<F: Fn() -> SystemTime + Send + Sync> Clock for F {
+    fn now(&self) -> SystemTime {
+        self()
+    }
+}
+
+impl Weekday {
+    pub const ALL: [Weekday; 7] = [
+        Weekday::Monday,
+        Weekday::Tuesday,
+        Weekday::Wednesday,
+        Weekday::Thursday,
+        Weekday::Friday,
+        Weekday::Saturday,
+        Weekday::Sunday,
+    ];
+    pub const WORKDAYS: [Weekday; 5] = [
+        Weekday::Monday,
+        Weekday::Tuesday,
+        Weekday::Wednesday,
+        Weekday::Thursday,
+        Weekday::Friday,
+    ];
+    pub const WEEKEND: [Weekday; 2] = [Weekday::Saturday, Weekday::Sunday];
+
+    /// The weekday of a number of days since 1970-01-01, a Thursday.
+    fn from_days(days: i64) -> Self {
+        Self::ALL[(days + 3).rem_euclid(7) as usize]
+    }
+}
+
+impl QuietWindow {
+    /// A window from `start` to `end` on `days`. Fails if an hour is over 23 or a minute over 59.
+    pub fn new(days: &[Weekday], start: (u8, u8), end: (u8, u8)) -> Result<Self, Box<dyn std::error::Error>> {
+        let window = Self {
+            days: days.to_vec(),
+            start,
+            end,
+        };
+        window.check()?;
+        Ok(window)
+    }
+
+    fn check(&self) -> Result<(), Box<dyn std::error::Error>> {
+        for (hour, minute) in [self.start, self.end] {
+            if hour > 23 || minute > 59 {
+                return Err(format!("Invalid time in quiet window: {:02}:{:02}", hour, minute).into());
+            }
+        }
+        Ok(())
+    }
+
+    /// The end of the window containing the local time `local`, in seconds since the epoch.
+    fn end_at(&self, local: i64) -> Option<i64> {
+        let minutes = |(hour, minute): (u8, u8)| hour as i64 * 60 + minute as i64;
+        let (start, end) = (minutes(self.start) * 60, minutes(self.end) * 60);
+        let length = match end > start {
+            true => end - start,
+            false => end - start + 24 * 60 * 60,
+        };
+        let today = local.div_euclid(24 * 60 * 60);
+        // A window started today or, running past midnight, yesterday
+        [today, today - 1].into_iter().find_map(|day| {
+            let window_start = day * 24 * 60 * 60 + start;
+            let started = self.days.contains(&Weekday::from_days(day));
+            (started && (window_start..window_start + length).contains(&local)).then_some(window_start + length)
+        })
+    }
+}
+
+impl QuietHours {
+    /// Quiet hours without windows, in UTC.
+    pub fn new(action: QuietAction) -> Self {
+        Self {
+            windows: Vec::new(),
+            action,
+            time_zone: TimeZone::Utc,
+            clock: Box::new(SystemTime::now),
+            offsets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn add_window(mut self, window: QuietWindow) -> Self {
+        self.windows.push(window);
+        self
+    }
+
+    pub fn set_time_zone(mut self, time_zone: TimeZone) -> Self {
+        self.time_zone = time_zone;
+        self
+    }
+
+    pub fn set_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    pub fn action(&self) -> QuietAction {
+        self.action
+    }
+
+    /// The end of the current quiet hours, or `None` if notifications are shown now.
+    ///
+    /// Windows that follow each other are joined.
+    pub fn active_until(&self) -> Result<Option<SystemTime>, Box<dyn std::error::Error>> {
+        self.active_until_with(&powershell::Options::default())
+    }
+
+    /// The end of the current quiet hours, reading the time zone with the timeout and cancellation of a notifier.
+    fn active_until_with(&self, options: &powershell::Options) -> Result<Option<SystemTime>, Box<dyn std::error::Error>> {
+        for window in &self.windows {
+            window.check()?;
+        }
+        let mut now = unix_seconds(self.clock.now());
+        let mut end = None;
+        // Bounded, a week of windows
+        for _ in 0..7 * 24 {
+            let offset = self.offset(now, options)? as i64 * 60;
+            let local = now + offset;
+            let Some(next) = self.windows.iter().filter_map(|window| window.end_at(local)).max() else {
+                break;
+            };
+            // Daylight saving time may start or end before the window does
+            let guess = next - offset;
+            let next = next - self.offset(guess, options)? as i64 * 60;
+            if next <= now {
+                break;
+            }
+            end = Some(next);
+            now = next;
+        }
+        Ok(end.map(from_unix_seconds))
+    }
+
+    /// Apply the quiet hours to a notification, returning the time to defer it to.
+    pub(crate) fn apply(
+        &self,
+        toast: &mut Cow<'_, WinToastNotify>,
+        options: &powershell::Options,
+    ) -> Result<Option<SystemTime>, Box<dyn std::error::Error>> {
+        if toast.bypass_quiet_hours {
+            return Ok(None);
+        }
+        let Some(end) = self.active_until_with(options)? else {
+            return Ok(None);
+        };
+        if self.action == QuietAction::Defer && toast.progress.is_none() {
+            return Ok(Some(end));
+        }
+        let toast = toast.to_mut();
+        if self.action == QuietAction::Downgrade {
+            toast.scenario = Scenario::None;
+            toast.duration = Duration::Short;
+            toast.priority = Priority::Default;
+        }
+        toast.audio = Some(Audio::Silent);
+        toast.audio_loop = Loop::False;
+        // Later attempts would sound an alarm
+        toast.escalation = None;
+        Ok(None)
+    }
+
+    /// The offset of the time zone at the time `now` in UTC, in minutes.
+    fn offset(&self, now: i64, options: &powershell::Options) -> Result<i32, Box<dyn std::error::Error>> {
+        let zone = match &self.time_zone {
+            TimeZone::Utc => return Ok(0),
+            TimeZone::Fixed(minutes) => return Ok(*minutes),
+            TimeZone::Local => String::from("[TimeZoneInfo]::Local"),
+            TimeZone::Named(id) => format!("[TimeZoneInfo]::FindSystemTimeZoneById({})", powershell::quote(id)),
+        };
+        let hour = now.div_euclid(60 * 60);
+        let mut offsets = self.offsets.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(offset) = offsets.get(&hour) {
+            return Ok(*offset);
+        }
+        let command = format!(
+            "$ErrorActionPreference = 'Stop'\n{}.GetUtcOffset([DateTimeOffset]::FromUnixTimeSeconds({})).TotalMinutes",
+            zone, now
+        );
+        let offset = powershell::run_with(&command, options)?
+            .trim()
+            .parse::<f64>()
+            .map_err(|e| format!("Failed to read the time zone offset: {}", e))? as i32;
+        if offsets.len() >= 7 * 24 {
+            offsets.clear();
+        }
+        offsets.insert(hour, offset);
+        Ok(offset)
+    }
+}
+
+impl WinToastNotify {
+    /// Show the notification during quiet hours, such as for critical alerts. Default is `false`.
+    pub fn set_bypass_quiet_hours(mut self, bypass: bool) -> Self {
+        self.bypass_quiet_hours = bypass;
+        self
+    }
+}
+
+fn unix_seconds(time: SystemTime) -> i64 {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(duration) => duration.as_secs() as i64,
+        Err(e) => -(e.duration().as_secs() as i64),
+    }
+}
+
+fn from_unix_seconds(seconds: i64) -> SystemTime {
+    match seconds >= 0 {
+        true => UNIX_EPOCH + std::time::Duration::from_secs(seconds as u64),
+        false => UNIX_EPOCH - std::time::Duration::from_secs(seconds.unsigned_abs()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Escalation;
+
+    /// Monday 2024-01-01 23:30 in UTC+1.
+    const MONDAY_NIGHT: u64 = 1704148200;
+    /// Tuesday 2024-01-02 07:00 in UTC+1.
+    const TUESDAY_MORNING: u64 = 1704175200;
+
+    fn quiet_hours(action: QuietAction) -> QuietHours {
+        let now = UNIX_EPOCH + std::time::Duration::from_secs(MONDAY_NIGHT);
+        QuietHours::new(action)
+            .add_window(QuietWindow::new(&Weekday::WORKDAYS, (22, 0), (7, 0)).unwrap())
+            .set_time_zone(TimeZone::Fixed(60))
+            .set_clock(move || now)
+    }
+
+    fn alarm() -> WinToastNotify {
+        WinToastNotify::new()
+            .set_title("Freezer door open")
+            .set_scenario(Scenario::Alarm)
+            .set_duration(Duration::Long)
+            .set_priority(Priority::High)
+            .set_audio(Audio::WinLoopingAlarm2, Loop::True)
+            .set_escalation(Escalation::new(std::time::Duration::from_secs(60), 3))
+    }
+
+    fn apply(quiet: &QuietHours, toast: &WinToastNotify) -> (WinToastNotify, Option<SystemTime>) {
+        let mut toast = Cow::Borrowed(toast);
+        let defer = quiet.apply(&mut toast, &powershell::Options::default()).unwrap();
+        (toast.into_owned(), defer)
+    }
+
+    #[test]
+    fn mutes() {
+        let (toast, defer) = apply(&quiet_hours(QuietAction::Mute), &alarm());
+        assert_eq!(defer, None);
+        assert!(matches!(toast.audio, Some(Audio::Silent)));
+        assert!(matches!(toast.audio_loop, Loop::False));
+        assert!(matches!(toast.scenario, Scenario::Alarm));
+        assert!(toast.escalation.is_none());
+    }
+
+    #[test]
+    fn downgrades() {
+        let (toast, defer) = apply(&quiet_hours(QuietAction::Downgrade), &alarm());
+        assert_eq!(defer, None);
+        assert!(matches!(toast.audio, Some(Audio::Silent)));
+        assert!(matches!(toast.scenario, Scenario::None));
+        assert!(matches!(toast.duration, Duration::Short));
+        assert!(matches!(toast.priority, Priority::Default));
+        assert!(toast.escalation.is_none());
+    }
+
+    #[test]
+    fn defers() {
+        let (toast, defer) = apply(&quiet_hours(QuietAction::Defer), &alarm());
+        assert_eq!(defer, Some(UNIX_EPOCH + std::time::Duration::from_secs(TUESDAY_MORNING)));
+        assert!(matches!(toast.audio, Some(Audio::WinLoopingAlarm2)));
+        assert!(toast.escalation.is_some());
+
+        // Progress cannot be scheduled
+        let progress = alarm().set_progress("backup", "Backup", "Copying", 0.5, "50%");
+        let (toast, defer) = apply(&quiet_hours(QuietAction::Defer), &progress);
+        assert_eq!(defer, None);
+        assert!(matches!(toast.audio, Some(Audio::Silent)));
+        assert!(toast.escalation.is_none());
+    }
+
+    #[test]
+    fn bypasses() {
+        let (toast, defer) = apply(&quiet_hours(QuietAction::Downgrade), &alarm().set_bypass_quiet_hours(true));
+        assert_eq!(defer, None);
+        assert!(matches!(toast.audio, Some(Audio::WinLoopingAlarm2)));
+        assert!(matches!(toast.scenario, Scenario::Alarm));
+    }
+
+    #[test]
+    fn outside_windows() {
+        let now = UNIX_EPOCH + std::time::Duration::from_secs(TUESDAY_MORNING);
+        let quiet = quiet_hours(QuietAction::Mute).set_clock(move || now);
+        assert_eq!(quiet.active_until().unwrap(), None);
+        let (toast, _) = apply(&quiet, &alarm());
+        assert!(matches!(toast.audio, Some(Audio::WinLoopingAlarm2)));
+    }
+
+    #[test]
+    fn ends_after_daylight_saving_time() {
+        // Saturday 2024-10-26 22:30 CEST, daylight saving time ends at 01:00 UTC
+        let now = UNIX_EPOCH + std::time::Duration::from_secs(1729974600);
+        let quiet = QuietHours::new(QuietAction::Defer)
+            .add_window(QuietWindow::new(&Weekday::ALL, (22, 0), (7, 0)).unwrap())
+            .set_time_zone(TimeZone::Named("W. Europe Standard Time".to_string()))
+            .set_clock(move || now);
+        {
+            let mut offsets = quiet.offsets.lock().unwrap();
+            for hour in 1729974600 / 3600 - 48..1729974600 / 3600 + 48 {
+                offsets.insert(hour, if hour < 1729990800 / 3600 { 120 } else { 60 });
+            }
+        }
+        // Sunday 07:00 CET
+        let end = UNIX_EPOCH + std::time::Duration::from_secs(1730008800);
+        assert_eq!(quiet.active_until().unwrap(), Some(end));
+    }
+
+    #[test]
+    fn rejects_invalid_windows() {
+        assert!(QuietWindow::new(&Weekday::ALL, (24, 0), (7, 0)).is_err());
+        assert!(QuietWindow::new(&Weekday::ALL, (22, 60), (7, 0)).is_err());
+        assert!(QuietWindow::new(&Weekday::ALL, (23, 59), (0, 0)).is_ok());
+
+        let window = QuietWindow {
+            days: Weekday::ALL.to_vec(),
+            start: (22, 0),
+            end: (31, 0),
+        };
+        assert!(QuietHours::new(QuietAction::Mute).add_window(window).active_until().is_err());
+    }
+}
@@ -0,0 +1,260 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+
+use crate::image_cache::content_hash;
+use crate::{Clock, WinToastNotify};
+
+/// The shortest delay of a notification, so that it is still in the future once PowerShell has started
+/// to schedule it.
+const MIN_DELAY: Duration = Duration::from_secs(5);
+
+/// Suppresses duplicate notifications and limits how many are shown, see
+/// [ToastNotifier::set_rate_limit](struct.ToastNotifier.html#method.set_rate_limit).
+///
+/// Notifications are duplicates if they have the same [fingerprint](struct.WinToastNotify.html#method.fingerprint).
+/// Limits are token buckets: a burst of `capacity` notifications, then one more per `interval`.
+/// The per-key limit applies to notifications with the same tag, or the same fingerprint if they have no tag.
+///
+/// # Examples
+/// ```
+/// use std::time::{Duration, SystemTime};
+/// use win_toast_notify::{Overflow, RateDecision, RateLimit, SuppressReason, WinToastNotify};
+///
+/// let now = SystemTime::now();
+/// let limit = RateLimit::new()
+///     .set_duplicate_window(Duration::from_secs(60))
+///     .set_global_limit(2, Duration::from_secs(30))
+///     .set_overflow(Overflow::Delay)
+///     .set_clock(move || now);
+///
+/// let down = WinToastNotify::new().set_title("api.example.com is down");
+/// assert_eq!(limit.check(&down), RateDecision::Send);
+/// assert_eq!(limit.check(&down), RateDecision::Suppress(SuppressReason::Duplicate));
+///
+/// let up = WinToastNotify::new().set_title("api.example.com is up");
+/// assert_eq!(limit.check(&up), RateDecision::Send);
+///
+/// let down_again = WinToastNotify::new().set_title("api.example.com is down").set_messages(vec!["Again"]);
+/// assert_eq!(limit.check(&down_again), RateDecision::Delay(now + Duration::from_secs(30)));
+/// ```
+pub struct RateLimit {
+    duplicate_window: Option<Duration>,
+    per_key: Option<(u32, Duration)>,
+    global: Option<(u32, Duration)>,
+    overflow: Overflow,
+    clock: Box<dyn Clock>,
+    state: Mutex<State>,
+}
+
+/// What happens to notifications over a limit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Overflow {
+    Suppress,
+    /// Schedule them for when the limit allows, at least 5 seconds later. Notifications with progress are
+    /// suppressed instead, since they cannot be scheduled.
+    Delay,
+}
+
+/// The decision of a [RateLimit](struct.RateLimit.html) for a notification.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RateDecision {
+    Send,
+    Suppress(SuppressReason),
+    Delay(SystemTime),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SuppressReason {
+    /// The same notification was sent within the duplicate window.
+    Duplicate,
+    /// The notification is over a limit.
+    RateLimited,
+    /// The channel of the notification is disabled, see [Channel](struct.Channel.html).
+    ChannelDisabled,
+    /// The notification was vetoed by [Middleware](trait.Middleware.html), for the reason.
+    Vetoed(String),
+}
+
+impl fmt::Display for SuppressReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SuppressReason::Duplicate => write!(f, "duplicate of a recent notification"),
+            SuppressReason::RateLimited => write!(f, "rate limit exceeded"),
+            SuppressReason::ChannelDisabled => write!(f, "channel disabled"),
+            SuppressReason::Vetoed(reason) => write!(f, "vetoed: {}", reason),
+        }
+    }
+}
+
+#[derive(Default)]
+struct State {
+    /// When each fingerprint was last sent.
+    sent: HashMap<String, SystemTime>,
+    global: Option<Bucket>,
+    per_key: HashMap<String, Bucket>,
+}
+
+/// Tokens left, negative when notifications were delayed, and when they were counted.
+#[derive(Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated: SystemTime,
+}
+
+impl Bucket {
+    fn full(capacity: u32, now: SystemTime) -> Self {
+        Self {
+            tokens: capacity as f64,
+            updated: now,
+        }
+    }
+
+    /// Add the tokens refilled since the last update.
+    fn refill(&mut self, (capacity, interval): (u32, Duration), now: SystemTime) {
+        let elapsed = now.duration_since(self.updated).unwrap_or_default();
+        self.tokens = (self.tokens + elapsed.as_secs_f64() / interval.as_secs_f64()).min(capacity as f64);
+        self.updated = now;
+    }
+
+    /// How long until a token is available.
+    fn wait(&self, (_, interval): (u32, Duration)) -> Duration {
+        match self.tokens >= 1.0 {
+            true => Duration::ZERO,
+            false => interval.mul_f64(1.0 - self.tokens),
+        }
+    }
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RateLimit {
+    /// No duplicate window and no limits, suppressing notifications over a limit once set.
+    pub fn new() -> Self {
+        Self {
+            duplicate_window: None,
+            per_key: None,
+            global: None,
+            overflow: Overflow::Suppress,
+            clock: Box::new(SystemTime::now),
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// Suppress notifications with the same fingerprint as one sent within `window`.
+    pub fn set_duplicate_window(mut self, window: Duration) -> Self {
+        self.duplicate_window = Some(window);
+        self
+    }
+
+    /// Limit notifications with the same tag, or the same fingerprint if they have no tag.
+    pub fn set_per_key_limit(mut self, capacity: u32, interval: Duration) -> Self {
+        self.per_key = Some((capacity.max(1), interval));
+        self
+    }
+
+    /// Limit all notifications.
+    pub fn set_global_limit(mut self, capacity: u32, interval: Duration) -> Self {
+        self.global = Some((capacity.max(1), interval));
+        self
+    }
+
+    pub fn set_overflow(mut self, overflow: Overflow) -> Self {
+        self.overflow = overflow;
+        self
+    }
+
+    pub fn set_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    /// Decide whether a notification is sent now, and count it if it is sent or delayed.
+    pub fn check(&self, toast: &WinToastNotify) -> RateDecision {
+        let now = self.clock.now();
+        let fingerprint = toast.fingerprint();
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+
+        if let Some(window) = self.duplicate_window {
+            state
+                .sent
+                .retain(|_, sent| now.duration_since(*sent).unwrap_or_default() < window);
+            if state.sent.contains_key(&fingerprint) {
+                return RateDecision::Suppress(SuppressReason::Duplicate);
+            }
+        }
+
+        let key = toast.effective_tag().map_or_else(|| fingerprint.clone(), str::to_string);
+        let mut wait = Duration::ZERO;
+        if let Some(limit) = self.global {
+            let bucket = state.global.get_or_insert_with(|| Bucket::full(limit.0, now));
+            bucket.refill(limit, now);
+            wait = wait.max(bucket.wait(limit));
+        }
+        if let Some(limit) = self.per_key {
+            // Full buckets are the same as no bucket
+            state.per_key.retain(|_, bucket| {
+                bucket.refill(limit, now);
+                bucket.tokens < limit.0 as f64
+            });
+            let bucket = state.per_key.entry(key.clone()).or_insert_with(|| Bucket::full(limit.0, now));
+            wait = wait.max(bucket.wait(limit));
+        }
+        let delay = !wait.is_zero();
+        if delay && (self.overflow == Overflow::Suppress || toast.progress.is_some()) {
+            return RateDecision::Suppress(SuppressReason::RateLimited);
+        }
+        if delay {
+            wait = wait.max(MIN_DELAY);
+        }
+
+        if let Some(bucket) = &mut state.global {
+            bucket.tokens -= 1.0;
+        }
+        if let Some(bucket) = state.per_key.get_mut(&key) {
+            bucket.tokens -= 1.0;
+        }
+        if self.duplicate_window.is_some() {
+            state.sent.insert(fingerprint, now + wait);
+        }
+        match delay {
+            true => RateDecision::Delay(now + wait),
+            false => RateDecision::Send,
+        }
+    }
+
+    /// Undo the counting of a notification that could not be delivered, so that it can be sent again.
+    pub(crate) fn refund(&self, toast: &WinToastNotify) {
+        let fingerprint = toast.fingerprint();
+        let key = toast.effective_tag().map_or_else(|| fingerprint.clone(), str::to_string);
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.sent.remove(&fingerprint);
+        if let (Some((capacity, _)), Some(bucket)) = (self.global, &mut state.global) {
+            bucket.tokens = (bucket.tokens + 1.0).min(capacity as f64);
+        }
+        if let (Some((capacity, _)), Some(bucket)) = (self.per_key, state.per_key.get_mut(&key)) {
+            bucket.tokens = (bucket.tokens + 1.0).min(capacity as f64);
+        }
+    }
+}
+
+impl WinToastNotify {
+    /// A hash of the title, messages and tag, identifying notifications with the same content.
+    pub fn fingerprint(&self) -> String {
+        let mut content = Vec::new();
+        for text in [&self.title, &self.tag] {
+            content.extend(text.as_deref().unwrap_or_default().as_bytes());
+            content.push(0);
+        }
+        for message in self.messages.iter().flatten() {
+            content.extend(message.as_bytes());
+            content.push(0);
+        }
+        content_hash(&content)
+    }
+}
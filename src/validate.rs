@@ -0,0 +1,392 @@
+use std::fmt;
+
+use crate::{audio, display, image_info, ActivationType, Audio, MediaSource, Scenario, ToastArguments, WinToastNotify};
+
+/// Maximum number of buttons in a notification.
+pub const MAX_ACTIONS: usize = 5;
+/// Maximum number of text elements (title and messages) in a notification.
+pub const MAX_TEXTS: usize = 3;
+/// Maximum file size of a notification image.
+pub const MAX_IMAGE_BYTES: u64 = 3 * 1024 * 1024;
+/// Maximum width and height of a notification image, in pixels.
+pub const MAX_IMAGE_DIMENSION: u32 = 1024;
+
+/// A problem found by [WinToastNotify::validate](struct.WinToastNotify.html#method.validate).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// The field the problem was found in, such as `actions[1].arguments`.
+    pub field: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    /// Windows will show the notification, but not as intended.
+    Warning,
+    /// Windows will drop the notification or part of it.
+    Error,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(f, "{}: {}: {}", severity, self.field, self.message)
+    }
+}
+
+impl WinToastNotify {
+    /// Check the notification against the toast schema and the limits of Windows.
+    ///
+    /// Notifications that break these rules are often dropped without any error, so this is
+    /// useful to run in tests or before [show](#method.show).
+    ///
+    /// # Examples
+    /// ```
+    /// use win_toast_notify::{WinToastNotify, Scenario, Severity};
+    ///
+    /// let diagnostics = WinToastNotify::new()
+    ///     .set_scenario(Scenario::Alarm)
+    ///     .set_title("Wake up")
+    ///     .set_messages(vec!["It's 7:00", "Breakfast is ready", "The bus leaves at 7:30"])
+    ///     .validate();
+    ///
+    /// assert!(diagnostics.iter().any(|d| d.field == "messages" && d.severity == Severity::Error));
+    /// assert!(diagnostics.iter().any(|d| d.field == "scenario" && d.severity == Severity::Warning));
+    /// ```
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        let mut report = |severity, field: &str, message: String| {
+            diagnostics.push(Diagnostic {
+                severity,
+                field: field.to_string(),
+                message,
+            })
+        };
+
+        let texts = self.title.iter().count() + self.messages.as_ref().map_or(0, Vec::len);
+        if texts > MAX_TEXTS {
+            report(
+                Severity::Error,
+                "messages",
+                format!("{} text elements including the title, only {} are shown", texts, MAX_TEXTS),
+            );
+        }
+
+        if let Some(open) = &self.open {
+            check_launch(&mut report, "open", open);
+        }
+
+        // Images are checked with the variants that may be shown instead
+        if let Some(logo) = &self.logo {
+            self.check_image(&mut report, "logo", logo);
+        }
+        if let Some(image) = &self.image {
+            self.check_image(&mut report, "image", image);
+        }
+        for (i, image) in self.images.iter().flatten().enumerate() {
+            self.check_image(&mut report, &format!("images[{}]", i), &image.source);
+        }
+
+        let actions = self.actions.as_deref().unwrap_or_default();
+        if actions.len() > MAX_ACTIONS {
+            report(
+                Severity::Error,
+                "actions",
+                format!("{} buttons, at most {} are allowed", actions.len(), MAX_ACTIONS),
+            );
+        }
+        for (i, action) in actions.iter().enumerate() {
+            let field = format!("actions[{}].arguments", i);
+            match action.activation_type {
+                ActivationType::Protocol => check_launch(&mut report, &field, &action.arguments),
+                _ => check_length(&mut report, &field, &action.arguments),
+            }
+            if let Some(image_url) = &action.image_url {
+                self.check_image(&mut report, &format!("actions[{}].image_url", i), image_url);
+            }
+        }
+
+        if actions.is_empty() {
+            let scenario = match self.scenario {
+                Scenario::Reminder => Some("Reminder"),
+                Scenario::Alarm => Some("Alarm"),
+                Scenario::IncomingCall => Some("IncomingCall"),
+                _ => None,
+            };
+            if let Some(scenario) = scenario {
+                report(
+                    Severity::Warning,
+                    "scenario",
+                    format!("Scenario::{} without buttons is shown as a normal notification", scenario),
+                );
+            }
+        }
+
+        if let Some(Audio::From(source)) = &self.audio {
+            if !audio::is_sound_event(source) {
+                if let Err(message) = audio::audio_uri(source) {
+                    report(Severity::Error, "audio", message);
+                }
+            }
+        }
+
+        if let Some(progress) = &self.progress {
+            if !(0.0..=1.0).contains(&progress.value) {
+                report(
+                    Severity::Error,
+                    "progress.value",
+                    format!("{} is outside of 0.0..=1.0", progress.value),
+                );
+            }
+        }
+
+        diagnostics
+    }
+
+    /// Check an image, or each of its variants for the display settings.
+    fn check_image(&self, report: &mut impl FnMut(Severity, &str, String), field: &str, source: &MediaSource) {
+        match source {
+            MediaSource::File(path) => {
+                for variant in display::possible_variants(path, self.display) {
+                    check_image(report, field, &MediaSource::File(variant));
+                }
+            }
+            _ => check_image(report, field, source),
+        }
+    }
+}
+
+fn check_length(report: &mut impl FnMut(Severity, &str, String), field: &str, arguments: &str) {
+    if arguments.len() > ToastArguments::MAX_LENGTH {
+        report(
+            Severity::Error,
+            field,
+            format!(
+                "{} bytes long, the limit is {}",
+                arguments.len(),
+                ToastArguments::MAX_LENGTH
+            ),
+        );
+    }
+}
+
+/// Check a URI or path opened by protocol activation.
+fn check_launch(report: &mut impl FnMut(Severity, &str, String), field: &str, launch: &str) {
+    check_length(report, field, launch);
+    // An empty launch is allowed, see `set_app_id`
+    if launch.is_empty() || is_path(launch) {
+        return;
+    }
+    match scheme(launch) {
+        Some(_) if launch.chars().any(|c| c.is_whitespace() || c.is_control()) => report(
+            Severity::Error,
+            field,
+            format!("\"{}\" is not a valid URI, spaces must be percent-encoded", launch),
+        ),
+        Some(_) => {}
+        None => report(
+            Severity::Error,
+            field,
+            format!("\"{}\" is neither an absolute URI nor an absolute path", launch),
+        ),
+    }
+}
+
+/// Check that a local image exists and is within the limits of Windows.
+fn check_image(report: &mut impl FnMut(Severity, &str, String), field: &str, source: &MediaSource) {
+    let (name, size) = match source {
+        MediaSource::File(path) => match path.metadata() {
+            Ok(metadata) => (format!("\"{}\"", path.display()), metadata.len()),
+            Err(_) => {
+                report(Severity::Error, field, format!("\"{}\" does not exist", path.display()));
+                return;
+            }
+        },
+        MediaSource::Bytes(bytes) => (String::from("The image"), bytes.len() as u64),
+        _ => return,
+    };
+    if size > MAX_IMAGE_BYTES {
+        report(
+            Severity::Error,
+            field,
+            format!("{} is {} bytes, the limit is {}", name, size, MAX_IMAGE_BYTES),
+        );
+        return;
+    }
+    let dimensions = match source {
+        MediaSource::File(path) => std::fs::read(path).ok().and_then(|bytes| image_info::dimensions(&bytes)),
+        MediaSource::Bytes(bytes) => image_info::dimensions(bytes),
+        _ => None,
+    };
+    let Some((width, height)) = dimensions else {
+        return;
+    };
+    if width > MAX_IMAGE_DIMENSION || height > MAX_IMAGE_DIMENSION {
+        report(
+            Severity::Error,
+            field,
+            format!(
+                "{} is {}x{} pixels, the limit is {}x{}",
+                name,
+                width,
+                height,
+                MAX_IMAGE_DIMENSION,
+                MAX_IMAGE_DIMENSION
+            ),
+        );
+    }
+}
+
+/// The scheme of an absolute URI. Drive letters such as `C:` are not schemes.
+fn scheme(s: &str) -> Option<&str> {
+    let (scheme, _) = s.split_once(':')?;
+    let mut chars = scheme.chars();
+    let valid = scheme.len() > 1
+        && chars.next()?.is_ascii_alphabetic()
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+    valid.then_some(scheme)
+}
+
+/// Whether `s` is an absolute Windows path, possibly starting with an environment variable.
+fn is_path(s: &str) -> bool {
+    let bytes = s.as_bytes();
+    let drive = bytes.len() >= 3
+        && bytes[0].is_ascii_alphabetic()
+        && bytes[1] == b':'
+        && matches!(bytes[2], b'\\' | b'/');
+    drive || s.starts_with(r"\\") || s.starts_with("$env:") || s.starts_with('%')
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::{notifier, Action, CropCircle, DisplaySettings, ImagePlacement};
+
+    /// A GIF header of the given size, padded to `len` bytes.
+    fn gif(width: u16, height: u16, len: usize) -> Vec<u8> {
+        let mut bytes = b"GIF89a".to_vec();
+        bytes.extend(width.to_le_bytes());
+        bytes.extend(height.to_le_bytes());
+        bytes.resize(len.max(bytes.len()), 0);
+        bytes
+    }
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("win-toast-notify-test-{}", notifier::unique_id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn errors(toast: &WinToastNotify, field: &str) -> Vec<String> {
+        toast
+            .validate()
+            .into_iter()
+            .filter(|d| d.field == field && d.severity == Severity::Error)
+            .map(|d| d.message)
+            .collect()
+    }
+
+    fn action(activation_type: ActivationType, arguments: &str) -> Action {
+        Action {
+            activation_type,
+            action_content: "Open".to_string(),
+            arguments: arguments.to_string(),
+            image_url: None,
+            button_style: None,
+        }
+    }
+
+    #[test]
+    fn checks_images() {
+        let dir = temp_dir();
+        fs::write(dir.join("small.gif"), gif(64, 64, 100)).unwrap();
+        fs::write(dir.join("wide.gif"), gif(2000, 10, 100)).unwrap();
+        fs::write(dir.join("heavy.gif"), gif(64, 64, MAX_IMAGE_BYTES as usize + 1)).unwrap();
+
+        let toast = WinToastNotify::new()
+            .set_logo(dir.join("small.gif"), CropCircle::False)
+            .set_image(dir.join("missing.gif"), ImagePlacement::Top)
+            .set_actions(vec![Action {
+                image_url: Some(dir.join("heavy.gif").into()),
+                ..action(ActivationType::Foreground, "open")
+            }]);
+        assert!(errors(&toast, "logo").is_empty());
+        assert!(errors(&toast, "image")[0].contains("does not exist"));
+        assert!(errors(&toast, "actions[0].image_url")[0].contains("bytes, the limit is"));
+
+        let toast = toast.set_logo(dir.join("wide.gif"), CropCircle::False);
+        assert!(errors(&toast, "logo")[0].contains("2000x10 pixels"));
+
+        let toast = toast.set_logo(gif(10, 1500, 100), CropCircle::False);
+        assert!(errors(&toast, "logo")[0].contains("10x1500 pixels"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn checks_variants() {
+        let dir = temp_dir();
+        fs::write(dir.join("logo.gif"), gif(64, 64, 100)).unwrap();
+        fs::write(dir.join("logo.theme-dark.gif"), gif(2000, 2000, 100)).unwrap();
+        // Not a variant
+        fs::write(dir.join("logo.backup.gif"), gif(2000, 2000, 100)).unwrap();
+
+        let toast = WinToastNotify::new().set_logo(dir.join("logo.gif"), CropCircle::False);
+        let logo = errors(&toast, "logo");
+        assert_eq!(logo.len(), 1);
+        assert!(logo[0].contains("logo.theme-dark.gif"));
+
+        let light = toast.set_display_settings(DisplaySettings::default());
+        assert!(errors(&light, "logo").is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn checks_launch_uris() {
+        let toast = WinToastNotify::new().set_open("https://example.com/a b");
+        assert!(errors(&toast, "open")[0].contains("percent-encoded"));
+        let toast = toast.set_open(r"reports\today.pdf");
+        assert!(errors(&toast, "open")[0].contains("neither an absolute URI nor an absolute path"));
+
+        for launch in ["https://example.com/?q=1", r"C:\Reports\today.pdf", r"\\server\share", r"$env:USERPROFILE\a"] {
+            assert!(errors(&WinToastNotify::new().set_open(launch), "open").is_empty(), "{}", launch);
+        }
+
+        let toast = WinToastNotify::new().set_actions(vec![
+            action(ActivationType::Protocol, "mailto:someone@example.com"),
+            action(ActivationType::Protocol, "not a uri"),
+        ]);
+        assert!(errors(&toast, "actions[0].arguments").is_empty());
+        assert_eq!(errors(&toast, "actions[1].arguments").len(), 1);
+    }
+
+    #[test]
+    fn checks_argument_length() {
+        let long = "a".repeat(ToastArguments::MAX_LENGTH + 1);
+        let toast = WinToastNotify::new().set_actions(vec![
+            action(ActivationType::Foreground, &"a".repeat(ToastArguments::MAX_LENGTH)),
+            action(ActivationType::Background, &long),
+            action(ActivationType::Protocol, &format!("https://example.com/{}", long)),
+        ]);
+        assert!(errors(&toast, "actions[0].arguments").is_empty());
+        assert!(errors(&toast, "actions[1].arguments")[0].contains("the limit is"));
+        assert!(errors(&toast, "actions[2].arguments")[0].contains("the limit is"));
+
+        let toast = WinToastNotify::new().set_actions(vec![action(ActivationType::Foreground, "a"); 6]);
+        assert!(errors(&toast, "actions")[0].contains("at most 5"));
+    }
+
+    #[test]
+    fn checks_progress() {
+        for (value, valid) in [(0.0, true), (1.0, true), (0.5, true), (-0.1, false), (1.5, false), (f32::NAN, false)] {
+            let toast = WinToastNotify::new().set_progress("backup", "Backup", "Copying", value, "");
+            assert_eq!(errors(&toast, "progress.value").is_empty(), valid, "{}", value);
+        }
+    }
+}